@@ -0,0 +1,237 @@
+//! Physical interfaces used to reach the HD44780 controller in 4-bit mode.
+
+use crate::hal::{Delay, I2cWrite, NoPin, OutputLine};
+use crate::{Error, Line};
+
+/// Transport able to latch 4bits packets into the LCD controller.
+pub trait DataBus {
+    type Error;
+
+    /// Latch the lower 4bits of `nibble`, either in the instruction (`data == false`) or data register.
+    fn write_nibble<D: Delay>(&mut self, data: bool, nibble: u8, delay: &mut D)
+                              -> Result<(), Error<Self::Error>>;
+
+    /// Switch the display backlight, if the bus controls it.
+    fn set_backlight(&mut self, on: bool) -> Result<(), Error<Self::Error>>;
+}
+
+/// Direct wiring of EN, RS and D4-D7 to GPIO pins (RW tied to ground), with an optional backlight pin.
+pub struct ParallelBus<EN, RS, D4, D5, D6, D7, BL> {
+    en: EN,
+    rs: RS,
+    d4: D4,
+    d5: D5,
+    d6: D6,
+    d7: D7,
+    backlight: BL,
+}
+
+impl<EN, RS, D4, D5, D6, D7, E> ParallelBus<EN, RS, D4, D5, D6, D7, NoPin<E>>
+    where EN: OutputLine<Error=E> {
+    pub fn new(en: EN, rs: RS, d4: D4, d5: D5, d6: D6, d7: D7) -> Self {
+        ParallelBus { en, rs, d4, d5, d6, d7, backlight: NoPin::new() }
+    }
+
+    /// Take control of the pin switching the backlight.
+    pub fn with_backlight<BL>(self, backlight: BL) -> ParallelBus<EN, RS, D4, D5, D6, D7, BL> {
+        let ParallelBus { en, rs, d4, d5, d6, d7, .. } = self;
+        ParallelBus { en, rs, d4, d5, d6, d7, backlight }
+    }
+}
+
+impl<EN, RS, D4, D5, D6, D7, BL> ParallelBus<EN, RS, D4, D5, D6, D7, BL> {
+    /// Give back the owned pins.
+    pub fn release(self) -> (EN, RS, D4, D5, D6, D7, BL) {
+        (self.en, self.rs, self.d4, self.d5, self.d6, self.d7, self.backlight)
+    }
+}
+
+impl<EN, RS, D4, D5, D6, D7, BL, E> DataBus for ParallelBus<EN, RS, D4, D5, D6, D7, BL>
+    where
+        EN: OutputLine<Error=E>, RS: OutputLine<Error=E>,
+        D4: OutputLine<Error=E>, D5: OutputLine<Error=E>,
+        D6: OutputLine<Error=E>, D7: OutputLine<Error=E>,
+        BL: OutputLine<Error=E> {
+    type Error = E;
+
+    fn write_nibble<D: Delay>(&mut self, data: bool, nibble: u8, delay: &mut D)
+                              -> Result<(), Error<E>> {
+        set_pin(&mut self.rs, Line::RS, data)?;
+        set_pin(&mut self.d4, Line::D4, (nibble & 0x1) > 0)?;
+        set_pin(&mut self.d5, Line::D5, (nibble & 0x2) > 0)?;
+        set_pin(&mut self.d6, Line::D6, (nibble & 0x4) > 0)?;
+        set_pin(&mut self.d7, Line::D7, (nibble & 0x8) > 0)?;
+
+        set_pin(&mut self.en, Line::EN, true)?;
+        delay.delay_ms(1); // enable pulse must be > 450ns
+        set_pin(&mut self.en, Line::EN, false)?;
+        delay.delay_ms(1); // commands need > 37us to settle
+        Ok(())
+    }
+
+    fn set_backlight(&mut self, on: bool) -> Result<(), Error<E>> {
+        set_pin(&mut self.backlight, Line::Backlight, on)
+    }
+}
+
+/// Drive a single line to the given level, tagging a failure with the line name.
+fn set_pin<P, E>(pin: &mut P, line: Line, high: bool)
+                 -> Result<(), Error<E>>
+    where P: OutputLine<Error=E> {
+    pin.set_level(high).map_err(|e| Error::Pin(line, e))
+}
+
+/// Default address of PCF8574 based backpacks (A0-A2 pulled up).
+pub const PCF8574_DEFAULT_ADDRESS: u8 = 0x27;
+
+// PCF8574 backpack wiring: P0 -> RS, P1 -> RW, P2 -> EN, P3 -> backlight, P4-P7 -> D4-D7
+const I2C_RS: u8 = 0x01;
+const I2C_EN: u8 = 0x04;
+const I2C_BACKLIGHT: u8 = 0x08;
+
+/// PCF8574 I2C backpack, the common "LCD1602 I2C" module.
+pub struct I2cBus<I2C> {
+    i2c: I2C,
+    address: u8,
+    backlight: bool,
+}
+
+impl<I2C> I2cBus<I2C> {
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        I2cBus { i2c, address, backlight: true }
+    }
+
+    /// Give back the owned I2C peripheral.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+}
+
+impl<I2C: I2cWrite> DataBus for I2cBus<I2C> {
+    type Error = I2C::Error;
+
+    fn write_nibble<D: Delay>(&mut self, data: bool, nibble: u8, delay: &mut D)
+                              -> Result<(), Error<I2C::Error>> {
+        let mut byte = (nibble & 0x0f) << 4;
+        if data { byte |= I2C_RS; }
+        if self.backlight { byte |= I2C_BACKLIGHT; }
+
+        self.i2c.write(self.address, &[byte | I2C_EN]).map_err(Error::Bus)?;
+        delay.delay_us(1); // enable pulse must be > 450ns
+        self.i2c.write(self.address, &[byte]).map_err(Error::Bus)?;
+        delay.delay_us(50); // commands need > 37us to settle
+        Ok(())
+    }
+
+    fn set_backlight(&mut self, on: bool) -> Result<(), Error<I2C::Error>> {
+        self.backlight = on;
+        let byte = if on { I2C_BACKLIGHT } else { 0x00 };
+        self.i2c.write(self.address, &[byte]).map_err(Error::Bus)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::hal::mock::{self, Log, MockError, Op};
+
+    type MockParallelBus = ParallelBus<mock::Pin, mock::Pin, mock::Pin, mock::Pin, mock::Pin, mock::Pin, mock::Pin>;
+
+    fn parallel_bus(log: &Log) -> MockParallelBus {
+        let pin = |name| mock::Pin::new(name, log);
+        ParallelBus::new(pin("EN"), pin("RS"), pin("D4"), pin("D5"), pin("D6"), pin("D7"))
+            .with_backlight(pin("BL"))
+    }
+
+    #[test]
+    fn parallel_nibble_is_set_up_then_strobed() {
+        let log = mock::log();
+        let mut bus = parallel_bus(&log);
+        bus.write_nibble(true, 0b1010, &mut mock::Delay(log.clone())).unwrap();
+        assert_eq!(*log.borrow(), vec![
+            Op::Pin("RS", true),
+            Op::Pin("D4", false),
+            Op::Pin("D5", true),
+            Op::Pin("D6", false),
+            Op::Pin("D7", true),
+            Op::Pin("EN", true),
+            Op::DelayMs(1),
+            Op::Pin("EN", false),
+            Op::DelayMs(1),
+        ]);
+    }
+
+    #[test]
+    fn parallel_nibble_keeps_the_lower_bits_only() {
+        let log = mock::log();
+        let mut bus = parallel_bus(&log);
+        bus.write_nibble(false, 0xf5, &mut mock::Delay(log.clone())).unwrap();
+        let levels: Vec<Op> = log.borrow()[..5].to_vec();
+        assert_eq!(levels, vec![
+            Op::Pin("RS", false),
+            Op::Pin("D4", true),
+            Op::Pin("D5", false),
+            Op::Pin("D6", true),
+            Op::Pin("D7", false),
+        ]);
+    }
+
+    #[test]
+    fn parallel_backlight_drives_its_pin() {
+        let log = mock::log();
+        let mut bus = parallel_bus(&log);
+        bus.set_backlight(false).unwrap();
+        assert_eq!(*log.borrow(), vec![Op::Pin("BL", false)]);
+    }
+
+    #[test]
+    fn parallel_failure_names_the_line() {
+        let log = mock::log();
+        let mut bus = parallel_bus(&log);
+        bus.d6.broken = true;
+        let error = bus.write_nibble(true, 0x0f, &mut mock::Delay(log.clone())).unwrap_err();
+        assert!(matches!(error, Error::Pin(Line::D6, MockError)));
+        // the strobe never happens on a half set up nibble
+        assert!(!log.borrow().contains(&Op::Pin("EN", true)));
+    }
+
+    #[test]
+    fn i2c_nibble_is_latched_by_pulsing_en() {
+        let log = mock::log();
+        let mut bus = I2cBus::new(mock::I2c(log.clone()), PCF8574_DEFAULT_ADDRESS);
+        bus.write_nibble(true, 0b1010, &mut mock::Delay(log.clone())).unwrap();
+        // D4-D7 on P4-P7, backlight (P3) on by default, RS (P0) set for data, EN (P2) pulsed
+        assert_eq!(*log.borrow(), vec![
+            Op::I2c(0x27, vec![0b1010_1101]),
+            Op::DelayUs(1),
+            Op::I2c(0x27, vec![0b1010_1001]),
+            Op::DelayUs(50),
+        ]);
+    }
+
+    #[test]
+    fn i2c_command_nibble_without_backlight() {
+        let log = mock::log();
+        let mut bus = I2cBus::new(mock::I2c(log.clone()), 0x3f);
+        bus.set_backlight(false).unwrap();
+        bus.write_nibble(false, 0xf3, &mut mock::Delay(log.clone())).unwrap();
+        assert_eq!(*log.borrow(), vec![
+            Op::I2c(0x3f, vec![0x00]),
+            Op::I2c(0x3f, vec![0b0011_0100]),
+            Op::DelayUs(1),
+            Op::I2c(0x3f, vec![0b0011_0000]),
+            Op::DelayUs(50),
+        ]);
+    }
+
+    #[test]
+    fn i2c_backlight_byte() {
+        let log = mock::log();
+        let mut bus = I2cBus::new(mock::I2c(log.clone()), PCF8574_DEFAULT_ADDRESS);
+        bus.set_backlight(true).unwrap();
+        assert_eq!(*log.borrow(), vec![Op::I2c(0x27, vec![0x08])]);
+    }
+}
@@ -0,0 +1,77 @@
+//! Common interface of character displays, so that applications do not depend on a specific panel.
+
+use crate::bus::DataBus;
+use crate::custom_characters::{CharMap, CUSTOM_CHARS_MAPS};
+use crate::hal::Delay;
+use crate::{Error, LCD1602};
+
+/// A display organised as a grid of characters, with up to 8 user defined glyphs.
+pub trait CharDisplay {
+    type Error;
+
+    /// Number of rows and columns, as `(rows, columns)`.
+    fn dimensions(&self) -> (u8, u8);
+
+    /// Clear screen and set cursor to start.
+    fn clear(&mut self) -> Result<(), Self::Error>;
+
+    /// Move the cursor to a given position.
+    fn set_cursor(&mut self, row: u8, column: u8) -> Result<(), Self::Error>;
+
+    /// Write a given string at the cursor position.
+    fn print(&mut self, s: &str) -> Result<(), Self::Error>;
+
+    /// Store a custom glyph at `location` (allowed [0-7]).
+    fn create_custom_char(&mut self, location: u8, char_map: CharMap) -> Result<(), Self::Error>;
+
+    /// Write a custom glyph that was previously created.
+    fn write_custom_char(&mut self, location: u8) -> Result<(), Self::Error>;
+
+    /// Switch the backlight on or off (or whatever the panel uses to look alike).
+    fn set_backlight(&mut self, on: bool) -> Result<(), Self::Error>;
+
+    /// Store all the pre-defined custom characters, at their own location.
+    fn init_custom_chars(&mut self) -> Result<(), Self::Error> {
+        for (location, char_map) in CUSTOM_CHARS_MAPS.iter().enumerate() {
+            self.create_custom_char(location as u8, *char_map)?;
+        }
+        Ok(())
+    }
+}
+
+impl<BUS, DELAY, E> CharDisplay for LCD1602<BUS, DELAY>
+    where BUS: DataBus<Error=E>, DELAY: Delay {
+    type Error = Error<E>;
+
+    fn dimensions(&self) -> (u8, u8) {
+        (2, 16)
+    }
+
+    fn clear(&mut self) -> Result<(), Error<E>> {
+        LCD1602::clear(self)
+    }
+
+    fn set_cursor(&mut self, row: u8, column: u8) -> Result<(), Error<E>> {
+        LCD1602::set_cursor(self, row, column)
+    }
+
+    fn print(&mut self, s: &str) -> Result<(), Error<E>> {
+        LCD1602::print(self, s)
+    }
+
+    fn create_custom_char(&mut self, location: u8, char_map: CharMap) -> Result<(), Error<E>> {
+        LCD1602::create_custom_char(self, location, char_map)
+    }
+
+    fn write_custom_char(&mut self, location: u8) -> Result<(), Error<E>> {
+        LCD1602::write_custom_char(self, location)
+    }
+
+    fn set_backlight(&mut self, on: bool) -> Result<(), Error<E>> {
+        LCD1602::set_backlight(self, on)
+    }
+
+    fn init_custom_chars(&mut self) -> Result<(), Error<E>> {
+        LCD1602::init_custom_chars(self)
+    }
+}
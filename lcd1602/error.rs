@@ -0,0 +1,79 @@
+//! Errors reported by the LCD driver.
+
+use core::fmt;
+
+/// Physical line of the LCD interface that took part in a failed transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Line {
+    EN,
+    RS,
+    RW,
+    D4,
+    D5,
+    D6,
+    D7,
+    Backlight,
+    I2C,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error<E> {
+    /// Driving a GPIO line failed.
+    Pin(Line, E),
+    /// The transport carrying the LCD bus (e.g. an I2C backpack) failed.
+    Bus(E),
+    /// Cursor coordinates outside of the display geometry.
+    InvalidCursorPosition { row: u8, column: u8 },
+    /// CGRAM holds only 8 custom characters, at locations [0-7].
+    InvalidCGRAMLocation(u8),
+    /// Character that cannot be encoded in the controller character ROM.
+    UnsupportedCharacter(char),
+}
+
+impl<E> Error<E> {
+    /// Line that failed, if the error comes from the hardware interface.
+    pub fn line(&self) -> Option<Line> {
+        match self {
+            Error::Pin(line, _) => Some(*line),
+            Error::Bus(_) => Some(Line::I2C),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Line::EN => "EN",
+            Line::RS => "RS",
+            Line::RW => "RW",
+            Line::D4 => "D4",
+            Line::D5 => "D5",
+            Line::D6 => "D6",
+            Line::D7 => "D7",
+            Line::Backlight => "backlight",
+            Line::I2C => "I2C",
+        };
+        f.write_str(name)
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Pin(line, e) => write!(f, "failed to drive {} pin: {:?}", line, e),
+            Error::Bus(e) => write!(f, "LCD bus transfer failed: {:?}", e),
+            Error::InvalidCursorPosition { row, column } => {
+                write!(f, "cursor position ({}, {}) is out of the display", row, column)
+            }
+            Error::InvalidCGRAMLocation(location) => {
+                write!(f, "CGRAM location {} is not in [0-7]", location)
+            }
+            Error::UnsupportedCharacter(ch) => {
+                write!(f, "character {:?} cannot be displayed", ch)
+            }
+        }
+    }
+}
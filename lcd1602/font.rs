@@ -0,0 +1,177 @@
+//! 5x8 font for graphic panels, mimicking the HD44780 character ROM.
+//! Each glyph is made of 5 columns, least significant bit on top.
+
+use crate::custom_characters::CharMap;
+
+/// First character stored in the font.
+pub const FIRST_CHAR: u8 = 0x20;
+/// Last character stored in the font ('→' and '←' are at 0x7e and 0x7f, as in the HD44780 ROM).
+pub const LAST_CHAR: u8 = 0x7f;
+
+pub type Glyph = [u8; 5];
+
+/// Whether a character has the same code in the HD44780 ROM and in the font, from ' ' up to '←' (0x7f).
+pub fn is_printable(ch: char) -> bool {
+    (FIRST_CHAR as char..=LAST_CHAR as char).contains(&ch)
+}
+
+pub const FONT_5X8: [Glyph; (LAST_CHAR - FIRST_CHAR + 1) as usize] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5f, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7f, 0x14, 0x7f, 0x14], // #
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1c, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1c, 0x00], // )
+    [0x08, 0x2a, 0x1c, 0x2a, 0x08], // *
+    [0x08, 0x08, 0x3e, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], // 0
+    [0x00, 0x42, 0x7f, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4b, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7f, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3c, 0x4a, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1e], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x00, 0x08, 0x14, 0x22, 0x41], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x41, 0x22, 0x14, 0x08, 0x00], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3e], // @
+    [0x7e, 0x11, 0x11, 0x11, 0x7e], // A
+    [0x7f, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3e, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], // D
+    [0x7f, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7f, 0x09, 0x09, 0x01, 0x01], // F
+    [0x3e, 0x41, 0x41, 0x51, 0x32], // G
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], // H
+    [0x00, 0x41, 0x7f, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3f, 0x01], // J
+    [0x7f, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7f, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7f, 0x02, 0x04, 0x02, 0x7f], // M
+    [0x7f, 0x04, 0x08, 0x10, 0x7f], // N
+    [0x3e, 0x41, 0x41, 0x41, 0x3e], // O
+    [0x7f, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3e, 0x41, 0x51, 0x21, 0x5e], // Q
+    [0x7f, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7f, 0x01, 0x01], // T
+    [0x3f, 0x40, 0x40, 0x40, 0x3f], // U
+    [0x1f, 0x20, 0x40, 0x20, 0x1f], // V
+    [0x7f, 0x20, 0x18, 0x20, 0x7f], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x03, 0x04, 0x78, 0x04, 0x03], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x00, 0x7f, 0x41, 0x41], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x41, 0x41, 0x7f, 0x00, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7f, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7f], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7e, 0x09, 0x01, 0x02], // f
+    [0x08, 0x14, 0x54, 0x54, 0x3c], // g
+    [0x7f, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7d, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3d, 0x00], // j
+    [0x00, 0x7f, 0x10, 0x28, 0x44], // k
+    [0x00, 0x41, 0x7f, 0x40, 0x00], // l
+    [0x7c, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7c, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7c, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7c], // q
+    [0x7c, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3f, 0x44, 0x40, 0x20], // t
+    [0x3c, 0x40, 0x40, 0x20, 0x7c], // u
+    [0x1c, 0x20, 0x40, 0x20, 0x1c], // v
+    [0x3c, 0x40, 0x30, 0x40, 0x3c], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0c, 0x50, 0x50, 0x50, 0x3c], // y
+    [0x44, 0x64, 0x54, 0x4c, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7f, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x08, 0x2a, 0x1c, 0x08], // →
+    [0x08, 0x1c, 0x2a, 0x08, 0x08], // ←
+];
+
+/// Font glyph of a character, if available.
+pub fn glyph(ch: u8) -> Option<&'static Glyph> {
+    if ch < FIRST_CHAR {
+        None
+    } else {
+        FONT_5X8.get((ch - FIRST_CHAR) as usize)
+    }
+}
+
+/// Turn an HD44780 CGRAM map (8 rows, 5 bits each, MSB on the left) into 5 font columns.
+pub fn from_char_map(char_map: &CharMap) -> Glyph {
+    let mut glyph = [0u8; 5];
+    for (column, bits) in glyph.iter_mut().enumerate() {
+        for (row, line) in char_map.iter().enumerate() {
+            if (line >> (4 - column)) & 0x1 > 0 {
+                *bits |= 1 << row;
+            }
+        }
+    }
+    glyph
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::custom_characters::{CUSTOM_CHARS_MAPS, HEART_FULL};
+
+    #[test]
+    fn glyphs_follow_the_character_codes() {
+        assert_eq!(glyph(b' '), Some(&[0; 5]));
+        assert_eq!(glyph(b'A'), Some(&[0x7e, 0x11, 0x11, 0x11, 0x7e]));
+        assert_eq!(glyph(b'z'), Some(&[0x44, 0x64, 0x54, 0x4c, 0x44]));
+        // Arrows where the HD44780 ROM has them
+        assert_eq!(glyph(0x7e), Some(&[0x08, 0x08, 0x2a, 0x1c, 0x08]));
+        assert_eq!(glyph(0x7f), Some(&[0x08, 0x1c, 0x2a, 0x08, 0x08]));
+    }
+
+    #[test]
+    fn unmapped_characters_have_no_glyph() {
+        assert_eq!(glyph(0x00), None);
+        assert_eq!(glyph(b'\n'), None);
+        assert_eq!(glyph(FIRST_CHAR - 1), None);
+        assert_eq!(glyph(LAST_CHAR + 1), None);
+        assert_eq!(glyph(0xff), None);
+
+        assert!(is_printable(' ') && is_printable('~') && is_printable('\x7f'));
+        assert!(!is_printable('\x1f') && !is_printable('\u{80}') && !is_printable('é'));
+    }
+
+    #[test]
+    fn char_maps_turn_into_columns() {
+        // Corner pixels: top-left is the least significant bit of the first column
+        assert_eq!(from_char_map(&[0x10, 0, 0, 0, 0, 0, 0, 0]), [0x01, 0, 0, 0, 0]);
+        assert_eq!(from_char_map(&[0, 0, 0, 0, 0, 0, 0, 0x01]), [0, 0, 0, 0, 0x80]);
+        assert_eq!(from_char_map(&[0x1f; 8]), [0xff; 5]);
+        // Only the 5 low bits of each row are pixels
+        assert_eq!(from_char_map(&[0xe0; 8]), [0; 5]);
+
+        assert_eq!(from_char_map(&CUSTOM_CHARS_MAPS[HEART_FULL as usize]), [0x18, 0x3c, 0x78, 0x3c, 0x18]);
+    }
+}
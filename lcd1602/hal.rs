@@ -0,0 +1,323 @@
+//! Bridge towards the embedded-hal trait family selected at build time.
+//! By default the driver is built against embedded-hal 0.2, while the `eh1` feature switches it
+//! to embedded-hal 1.0 (`digital::OutputPin`, `delay::DelayNs` and `i2c::I2c`).
+
+use core::marker::PhantomData;
+
+/// A GPIO line that can be driven high or low.
+pub trait OutputLine {
+    type Error;
+
+    fn set_level(&mut self, high: bool) -> Result<(), Self::Error>;
+}
+
+/// Placeholder for an optional line that is not wired, failing with the same error type as its siblings.
+pub struct NoPin<E>(PhantomData<E>);
+
+impl<E> NoPin<E> {
+    pub fn new() -> Self {
+        NoPin(PhantomData)
+    }
+}
+
+impl<E> Default for NoPin<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> OutputLine for NoPin<E> {
+    type Error = E;
+
+    fn set_level(&mut self, _high: bool) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// A blocking delay provider.
+pub trait Delay {
+    fn delay_us(&mut self, us: u16);
+    fn delay_ms(&mut self, ms: u16);
+}
+
+/// An I2C master able to write a buffer to a 7-bit address.
+pub trait I2cWrite {
+    type Error;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+#[cfg(not(feature = "eh1"))]
+mod eh02 {
+    use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+    use embedded_hal::blocking::i2c::Write;
+    use embedded_hal::digital::v2::OutputPin;
+
+    use super::{Delay, I2cWrite, OutputLine};
+
+    impl<P: OutputPin> OutputLine for P {
+        type Error = P::Error;
+
+        fn set_level(&mut self, high: bool) -> Result<(), Self::Error> {
+            if high { self.set_high() } else { self.set_low() }
+        }
+    }
+
+    impl<D: DelayUs<u16> + DelayMs<u16>> Delay for D {
+        fn delay_us(&mut self, us: u16) {
+            DelayUs::delay_us(self, us);
+        }
+
+        fn delay_ms(&mut self, ms: u16) {
+            DelayMs::delay_ms(self, ms);
+        }
+    }
+
+    impl<I: Write> I2cWrite for I {
+        type Error = I::Error;
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+            Write::write(self, address, bytes)
+        }
+    }
+}
+
+#[cfg(feature = "eh1")]
+mod eh1 {
+    use embedded_hal_1::delay::DelayNs;
+    use embedded_hal_1::digital::OutputPin;
+    use embedded_hal_1::i2c::I2c;
+
+    use super::{Delay, I2cWrite, OutputLine};
+
+    impl<P: OutputPin> OutputLine for P {
+        type Error = P::Error;
+
+        fn set_level(&mut self, high: bool) -> Result<(), Self::Error> {
+            if high { self.set_high() } else { self.set_low() }
+        }
+    }
+
+    impl<D: DelayNs> Delay for D {
+        fn delay_us(&mut self, us: u16) {
+            DelayNs::delay_us(self, us as u32);
+        }
+
+        fn delay_ms(&mut self, ms: u16) {
+            DelayNs::delay_ms(self, ms as u32);
+        }
+    }
+
+    impl<I: I2c> I2cWrite for I {
+        type Error = I::Error;
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+            I2c::write(self, address, bytes)
+        }
+    }
+}
+
+/// Pins, delay and I2C bus recording what the driver does with them, in the trait family of the build.
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    /// Something the driver did, in the order it was done.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Op {
+        Pin(&'static str, bool),
+        DelayUs(u32),
+        DelayMs(u32),
+        I2c(u8, Vec<u8>),
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct MockError;
+
+    pub type Log = Rc<RefCell<Vec<Op>>>;
+
+    pub fn log() -> Log {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    pub struct Pin {
+        pub name: &'static str,
+        pub log: Log,
+        /// Fail any attempt to drive the pin.
+        pub broken: bool,
+    }
+
+    impl Pin {
+        pub fn new(name: &'static str, log: &Log) -> Self {
+            Pin { name, log: log.clone(), broken: false }
+        }
+
+        fn set(&mut self, high: bool) -> Result<(), MockError> {
+            if self.broken {
+                return Err(MockError);
+            }
+            self.log.borrow_mut().push(Op::Pin(self.name, high));
+            Ok(())
+        }
+    }
+
+    pub struct Delay(pub Log);
+
+    pub struct I2c(pub Log);
+
+    #[cfg(not(feature = "eh1"))]
+    mod eh02 {
+        use embedded_hal::blocking::delay::{DelayMs, DelayUs};
+        use embedded_hal::blocking::i2c::Write;
+        use embedded_hal::digital::v2::OutputPin;
+
+        use super::{Delay, I2c, MockError, Op, Pin};
+
+        impl OutputPin for Pin {
+            type Error = MockError;
+
+            fn set_low(&mut self) -> Result<(), MockError> {
+                self.set(false)
+            }
+
+            fn set_high(&mut self) -> Result<(), MockError> {
+                self.set(true)
+            }
+        }
+
+        impl DelayUs<u16> for Delay {
+            fn delay_us(&mut self, us: u16) {
+                self.0.borrow_mut().push(Op::DelayUs(us as u32));
+            }
+        }
+
+        impl DelayMs<u16> for Delay {
+            fn delay_ms(&mut self, ms: u16) {
+                self.0.borrow_mut().push(Op::DelayMs(ms as u32));
+            }
+        }
+
+        impl Write for I2c {
+            type Error = MockError;
+
+            fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), MockError> {
+                self.0.borrow_mut().push(Op::I2c(address, bytes.to_vec()));
+                Ok(())
+            }
+        }
+    }
+
+    #[cfg(feature = "eh1")]
+    mod eh1 {
+        use embedded_hal_1::delay::DelayNs;
+        use embedded_hal_1::digital::{self, OutputPin};
+        use embedded_hal_1::i2c::{self, Operation};
+
+        use super::{Delay, I2c, MockError, Op, Pin};
+
+        impl digital::Error for MockError {
+            fn kind(&self) -> digital::ErrorKind {
+                digital::ErrorKind::Other
+            }
+        }
+
+        impl i2c::Error for MockError {
+            fn kind(&self) -> i2c::ErrorKind {
+                i2c::ErrorKind::Other
+            }
+        }
+
+        impl digital::ErrorType for Pin {
+            type Error = MockError;
+        }
+
+        impl OutputPin for Pin {
+            fn set_low(&mut self) -> Result<(), MockError> {
+                self.set(false)
+            }
+
+            fn set_high(&mut self) -> Result<(), MockError> {
+                self.set(true)
+            }
+        }
+
+        impl DelayNs for Delay {
+            fn delay_ns(&mut self, ns: u32) {
+                self.0.borrow_mut().push(Op::DelayUs(ns / 1000));
+            }
+
+            fn delay_us(&mut self, us: u32) {
+                self.0.borrow_mut().push(Op::DelayUs(us));
+            }
+
+            fn delay_ms(&mut self, ms: u32) {
+                self.0.borrow_mut().push(Op::DelayMs(ms));
+            }
+        }
+
+        impl i2c::ErrorType for I2c {
+            type Error = MockError;
+        }
+
+        impl i2c::I2c for I2c {
+            fn transaction(&mut self, address: u8, operations: &mut [Operation<'_>]) -> Result<(), MockError> {
+                for operation in operations {
+                    if let Operation::Write(bytes) = operation {
+                        self.0.borrow_mut().push(Op::I2c(address, bytes.to_vec()));
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use super::mock::{self, MockError, Op};
+    use super::{Delay, I2cWrite, NoPin, OutputLine};
+
+    #[test]
+    fn output_line_drives_the_pin() {
+        let log = mock::log();
+        let mut pin = mock::Pin::new("EN", &log);
+        pin.set_level(true).unwrap();
+        pin.set_level(false).unwrap();
+        assert_eq!(*log.borrow(), vec![Op::Pin("EN", true), Op::Pin("EN", false)]);
+    }
+
+    #[test]
+    fn output_line_reports_pin_errors() {
+        let log = mock::log();
+        let mut pin = mock::Pin { broken: true, ..mock::Pin::new("EN", &log) };
+        assert_eq!(pin.set_level(true), Err(MockError));
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    fn no_pin_does_nothing() {
+        let mut pin = NoPin::<MockError>::new();
+        assert_eq!(pin.set_level(true), Ok(()));
+    }
+
+    #[test]
+    fn delay_keeps_units() {
+        let log = mock::log();
+        let mut delay = mock::Delay(log.clone());
+        Delay::delay_us(&mut delay, 50);
+        Delay::delay_ms(&mut delay, 5);
+        assert_eq!(*log.borrow(), vec![Op::DelayUs(50), Op::DelayMs(5)]);
+    }
+
+    #[test]
+    fn i2c_write_reaches_the_address() {
+        let log = mock::log();
+        let mut i2c = mock::I2c(log.clone());
+        I2cWrite::write(&mut i2c, 0x27, &[0x0c, 0x08]).unwrap();
+        assert_eq!(*log.borrow(), vec![Op::I2c(0x27, vec![0x0c, 0x08])]);
+    }
+}
@@ -1,9 +1,8 @@
-use embedded_hal::digital::v2::OutputPin;
-use embedded_hal::prelude::_embedded_hal_blocking_delay_DelayMs;
-use stm32f7xx_hal::timer::SysDelay;
-
+use crate::bus::{DataBus, I2cBus, ParallelBus};
 use crate::custom_characters::{CharMap, MAN_STANDING, MAN_DANCING, HEART_BORDER, HEART_FULL, CUSTOM_CHARS_MAPS};
-use crate::{LCD1602, DelayMs, Error, TextDirection};
+use crate::font;
+use crate::hal::{Delay, I2cWrite, NoPin, OutputLine};
+use crate::{LCD1602, Error, TextDirection};
 use crate::lcd1602::PackType::{Command, Data};
 
 enum PackType {
@@ -11,29 +10,59 @@ enum PackType {
     Data,
 }
 
-impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
+impl<EN, RS, D4, D5, D6, D7, E, DELAY> LCD1602<ParallelBus<EN, RS, D4, D5, D6, D7, NoPin<E>>, DELAY>
     where
-        EN: OutputPin<Error=E>, RS: OutputPin<Error=E>,
-        D4: OutputPin<Error=E>, D5: OutputPin<Error=E>,
-        D6: OutputPin<Error=E>, D7: OutputPin<Error=E> {
-    /// Create and initialise a new LCD1602 interface.
-    pub fn new(en: EN, rs: RS, d4: D4, d5: D5, d6: D6, d7: D7, delay_handler: SysDelay)
-               -> Result<LCD1602<EN, RS, D4, D5, D6, D7>, Error<E>> {
-        let mut lcd = LCD1602 { en, rs, d4, d5, d6, d7, delay_handler };
+        EN: OutputLine<Error=E>, RS: OutputLine<Error=E>,
+        D4: OutputLine<Error=E>, D5: OutputLine<Error=E>,
+        D6: OutputLine<Error=E>, D7: OutputLine<Error=E>,
+        DELAY: Delay {
+    /// Create and initialise a new LCD1602 interface, wired in parallel 4-bit mode.
+    pub fn new(en: EN, rs: RS, d4: D4, d5: D5, d6: D6, d7: D7, delay_handler: DELAY)
+               -> Result<Self, Error<E>> {
+        Self::with_bus(ParallelBus::new(en, rs, d4, d5, d6, d7), delay_handler)
+    }
+}
+
+impl<I2C, DELAY> LCD1602<I2cBus<I2C>, DELAY>
+    where I2C: I2cWrite, DELAY: Delay {
+    /// Create and initialise a new LCD1602 interface, behind a PCF8574 I2C backpack.
+    pub fn new_i2c(i2c: I2C, address: u8, delay_handler: DELAY)
+                   -> Result<Self, Error<I2C::Error>> {
+        Self::with_bus(I2cBus::new(i2c, address), delay_handler)
+    }
+}
+
+impl<BUS, DELAY, E> LCD1602<BUS, DELAY>
+    where BUS: DataBus<Error=E>, DELAY: Delay {
+    /// Create and initialise a new LCD1602 interface over the given bus.
+    pub fn with_bus(bus: BUS, delay_handler: DELAY)
+                    -> Result<Self, Error<E>> {
+        let mut lcd = LCD1602 { bus, delay_handler };
         lcd.init()?;
         Ok(lcd)
     }
 
+    /// Switch the backlight on or off, if it is controlled through the bus.
+    pub fn set_backlight(&mut self, on: bool)
+                         -> Result<(), Error<E>> {
+        self.bus.set_backlight(on)
+    }
+
+    /// Give back the bus and the delay handler.
+    pub fn release(self) -> (BUS, DELAY) {
+        (self.bus, self.delay_handler)
+    }
+
     /// Initialise the LCD with default configurations.
     fn init(&mut self)
             -> Result<(), Error<E>> {
         // make 3 pings to the LCD to initialise communication for 4-bit mode
         self.send(Command, 0x03)?;
-        self.delay_ms(5u8);
+        self.delay_handler.delay_ms(5);
         self.send(Command, 0x03)?;
-        self.delay_ms(5u8);
+        self.delay_handler.delay_ms(5);
         self.send(Command, 0x03)?;
-        self.delay_ms(5u8);
+        self.delay_handler.delay_ms(5);
         self.send(Command, 0x02)?; // 4-bit mode
 
         let mut config_cmd = 0x00; // 5x8 dots per character
@@ -83,7 +112,7 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     pub fn clear(&mut self)
                  -> Result<(), Error<E>> {
         self.send(Command, 0x01)?;
-        self.delay_ms(2u8); // slowest displays need at least 1.53ms
+        self.delay_handler.delay_ms(2); // slowest displays need at least 1.53ms
         Ok(())
     }
 
@@ -91,7 +120,7 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     pub fn home(&mut self)
                 -> Result<(), Error<E>> {
         self.send(Command, 0x02)?;
-        self.delay_ms(2u8); // slowest displays need at least 1.53ms
+        self.delay_handler.delay_ms(2); // slowest displays need at least 1.53ms
         Ok(())
     }
 
@@ -99,9 +128,9 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     pub fn set_cursor(&mut self, row: u8, column: u8)
                    -> Result<(), Error<E>> {
         if column >= 16 || row >= 2 {
-            Err(Error::InvalidCursorPosition)
+            Err(Error::InvalidCursorPosition { row, column })
         } else {
-            self.send(Command, column + (row << 6) | 0x80)?; // set DDRAM address with coordinates
+            self.send(Command, (column + (row << 6)) | 0x80)?; // set DDRAM address with coordinates
             Ok(())
         }
     }
@@ -110,6 +139,10 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     pub fn print(&mut self, s: &str)
                  -> Result<(), Error<E>> {
         for ch in s.chars() {
+            // only printable ASCII (and the arrows past it) shares its code with the character ROM
+            if !font::is_printable(ch) {
+                return Err(Error::UnsupportedCharacter(ch));
+            }
             self.send(Data, ch as u8)?;
         }
         Ok(())
@@ -119,7 +152,7 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     pub fn create_custom_char(&mut self, mem_location: u8, char_map: CharMap)
                               -> Result<(), Error<E>> {
         if mem_location > 7 {
-            Err(Error::InvalidCGRAMLocation)
+            Err(Error::InvalidCGRAMLocation(mem_location))
         } else {
             self.send(Command, 0x40 | (mem_location << 3))?; // set CGRAM address
             for c in char_map {
@@ -133,7 +166,7 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     pub fn write_custom_char(&mut self, mem_location: u8)
                              -> Result<(), Error<E>> {
         if mem_location > 7 {
-            Err(Error::InvalidCGRAMLocation)
+            Err(Error::InvalidCGRAMLocation(mem_location))
         } else {
             self.send(Data, mem_location)
         }
@@ -142,51 +175,44 @@ impl<EN, RS, D4, D5, D6, D7, E> LCD1602<EN, RS, D4, D5, D6, D7>
     /// Send desired 8bits, either as command or data, as two 4bits packets through the bus.
     fn send(&mut self, comm_type: PackType, payload: u8)
             -> Result<(), Error<E>> {
-        match comm_type {
-            Command => self.rs.set_low()?, // write in instruction register
-            Data => self.rs.set_high()?, // write in data register
-        }
-        self.write_bus(payload >> 4)?;
-        self.write_bus(payload)?;
-        Ok(())
-    }
-
-    /// Write 4bits data in D4-D7 pins.
-    fn write_bus(&mut self, data: u8)
-                 -> Result<(), Error<E>> {
-        match (data & 0x1) > 0 {
-            true => self.d4.set_high()?,
-            false => self.d4.set_low()?,
+        let data = match comm_type {
+            Command => false, // write in instruction register
+            Data => true, // write in data register
         };
-        match (data & 0x2) > 0 {
-            true => self.d5.set_high()?,
-            false => self.d5.set_low()?,
-        };
-        match (data & 0x4) > 0 {
-            true => self.d6.set_high()?,
-            false => self.d6.set_low()?,
-        };
-        match (data & 0x8) > 0 {
-            true => self.d7.set_high()?,
-            false => self.d7.set_low()?,
-        };
-
-        self.en.set_high()?;
-        self.delay_ms(1u8); // enable pulse must be > 450ns
-        self.en.set_low()?;
-        self.delay_ms(1u8); // commands need > 37us to settle
+        self.bus.write_nibble(data, payload >> 4, &mut self.delay_handler)?;
+        self.bus.write_nibble(data, payload, &mut self.delay_handler)?;
         Ok(())
     }
 }
 
-impl<EN, RS, D4, D5, D6, D7> DelayMs<u8> for LCD1602<EN, RS, D4, D5, D6, D7> {
-    fn delay_ms(&mut self, ms: u8) -> () {
-        self.delay_handler.delay_ms(ms);
+#[cfg(test)]
+mod tests {
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::hal::mock::{self, Op};
+
+    #[test]
+    fn print_sends_the_left_arrow_as_data() {
+        let log = mock::log();
+        let mut lcd = LCD1602::new_i2c(mock::I2c(log.clone()), 0x27, mock::Delay(log.clone())).unwrap();
+        log.borrow_mut().clear();
+
+        lcd.print("\x7f").unwrap();
+        let writes: Vec<Op> = log.borrow().iter().filter(|op| matches!(op, Op::I2c(..))).cloned().collect();
+        assert_eq!(writes, vec![
+            Op::I2c(0x27, vec![0x7d]),
+            Op::I2c(0x27, vec![0x79]),
+            Op::I2c(0x27, vec![0xfd]),
+            Op::I2c(0x27, vec![0xf9]),
+        ]);
     }
-}
 
-impl<EN, RS, D4, D5, D6, D7> DelayMs<u16> for LCD1602<EN, RS, D4, D5, D6, D7> {
-    fn delay_ms(&mut self, ms: u16) -> () {
-        self.delay_handler.delay_ms(ms);
+    #[test]
+    fn print_rejects_control_characters() {
+        let log = mock::log();
+        let mut lcd = LCD1602::new_i2c(mock::I2c(log.clone()), 0x27, mock::Delay(log.clone())).unwrap();
+        assert!(matches!(lcd.print("\n"), Err(Error::UnsupportedCharacter('\n'))));
     }
 }
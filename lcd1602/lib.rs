@@ -1,42 +1,41 @@
 //! # LCD1602
 //! A simple embedded-hal driver for a 1602 LCD screens.
+//! Other character displays (SSD1306 OLEDs, an in-memory buffer) are available through the common
+//! [`CharDisplay`] trait.
+//!
+//! Built against embedded-hal 0.2 by default, or against embedded-hal 1.0 with the `eh1` feature.
 
 #![no_std]
 
-use stm32f7xx_hal::timer::SysDelay;
+#[cfg(test)]
+extern crate std;
+
+// embedded-hal 0.2 is unused with `eh1`, but stays a dependency: features can only add some
+#[cfg(feature = "eh1")]
+use embedded_hal as _;
 
 mod lcd1602;
+mod error;
+pub mod bus;
+pub mod char_display;
 pub mod custom_characters;
-
-pub struct LCD1602<EN, RS, D4, D5, D6, D7> {
-    en: EN,
-    rs: RS,
-    d4: D4,
-    d5: D5,
-    d6: D6,
-    d7: D7,
-    delay_handler: SysDelay, // TODO: take a function pointer to call for delays, if possible
-}
-
-pub trait DelayMs<UintType> {
-    fn delay_ms(&mut self, ms: UintType);
+pub mod font;
+pub mod hal;
+pub mod ssd1306;
+pub mod text_buffer;
+
+pub use bus::{DataBus, I2cBus, ParallelBus};
+pub use char_display::CharDisplay;
+pub use error::{Error, Line};
+pub use ssd1306::Ssd1306;
+pub use text_buffer::TextBuffer;
+
+pub struct LCD1602<BUS, DELAY> {
+    bus: BUS,
+    delay_handler: DELAY,
 }
 
 pub enum TextDirection {
     LeftToRight,
     RightToLeft,
 }
-
-#[derive(Debug)]
-pub enum Error<GPIO> {
-    GPIOError(GPIO),
-    InvalidCursorPosition,
-    InvalidCGRAMLocation,
-}
-
-/// Implement 'From' for the custom Error type defined above.
-impl<E> From<E> for Error<E> {
-    fn from(gpio_err: E) -> Self {
-        Self::GPIOError(gpio_err)
-    }
-}
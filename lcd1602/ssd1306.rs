@@ -0,0 +1,252 @@
+//! Character mode renderer for SSD1306 based 128x64 I2C OLED panels.
+//! Text is drawn with the built-in 5x8 font in cells of 6x8 pixels, giving 8 rows of 21 characters.
+
+use crate::char_display::CharDisplay;
+use crate::custom_characters::CharMap;
+use crate::font::{self, Glyph};
+use crate::hal::I2cWrite;
+use crate::Error;
+
+/// Default address of SSD1306 modules (SA0 pulled down).
+pub const SSD1306_DEFAULT_ADDRESS: u8 = 0x3c;
+
+const WIDTH: u8 = 128;
+const CELL_WIDTH: u8 = 6;
+const ROWS: u8 = 8; // one row per 8 pixels page
+const COLUMNS: u8 = WIDTH / CELL_WIDTH;
+
+// Control byte prefixing every I2C transfer
+const COMMAND: u8 = 0x00;
+const DATA: u8 = 0x40;
+
+pub struct Ssd1306<I2C> {
+    i2c: I2C,
+    address: u8,
+    row: u8,
+    column: u8,
+    custom_glyphs: [Glyph; 8],
+}
+
+impl<I2C: I2cWrite> Ssd1306<I2C> {
+    /// Create and initialise a new SSD1306 interface.
+    pub fn new(i2c: I2C, address: u8)
+               -> Result<Ssd1306<I2C>, Error<I2C::Error>> {
+        let mut oled = Ssd1306 { i2c, address, row: 0, column: 0, custom_glyphs: [[0; 5]; 8] };
+        oled.init()?;
+        Ok(oled)
+    }
+
+    /// Give back the owned I2C peripheral.
+    pub fn release(self) -> I2C {
+        self.i2c
+    }
+
+    /// Initialise the panel with page addressing, as needed to draw one character row per page.
+    fn init(&mut self)
+            -> Result<(), Error<I2C::Error>> {
+        self.command(&[0xae])?; // display off
+        self.command(&[0xd5, 0x80])?; // clock divider
+        self.command(&[0xa8, 0x3f])?; // 64 lines multiplex
+        self.command(&[0xd3, 0x00])?; // no display offset
+        self.command(&[0x40])?; // start line 0
+        self.command(&[0x8d, 0x14])?; // enable charge pump
+        self.command(&[0x20, 0x02])?; // page addressing mode
+        self.command(&[0xa1, 0xc8])?; // flip segments and COM scan, so that (0, 0) is top-left
+        self.command(&[0xda, 0x12])?; // COM pins configuration for 128x64
+        self.command(&[0x81, 0xcf])?; // contrast
+        self.command(&[0xd9, 0xf1])?; // pre-charge period
+        self.command(&[0xdb, 0x40])?; // VCOMH deselect level
+        self.command(&[0xa4, 0xa6])?; // show RAM content, not inverted
+        self.clear()?;
+        self.command(&[0xaf]) // display on
+    }
+
+    fn command(&mut self, cmd: &[u8])
+               -> Result<(), Error<I2C::Error>> {
+        for byte in cmd {
+            self.i2c.write(self.address, &[COMMAND, *byte]).map_err(Error::Bus)?;
+        }
+        Ok(())
+    }
+
+    /// Draw a cell at the cursor position and move the cursor forward, clipping at the end of the row.
+    fn draw(&mut self, glyph: Glyph)
+            -> Result<(), Error<I2C::Error>> {
+        if self.column >= COLUMNS {
+            return Ok(());
+        }
+        let x = self.column * CELL_WIDTH;
+        self.command(&[0xb0 | self.row, x & 0x0f, 0x10 | (x >> 4)])?;
+
+        let mut payload = [0u8; 1 + CELL_WIDTH as usize];
+        payload[0] = DATA;
+        payload[1..6].copy_from_slice(&glyph); // last column stays blank as spacing
+        self.i2c.write(self.address, &payload).map_err(Error::Bus)?;
+        self.column += 1;
+        Ok(())
+    }
+}
+
+impl<I2C: I2cWrite> CharDisplay for Ssd1306<I2C> {
+    type Error = Error<I2C::Error>;
+
+    fn dimensions(&self) -> (u8, u8) {
+        (ROWS, COLUMNS)
+    }
+
+    fn clear(&mut self) -> Result<(), Self::Error> {
+        let mut payload = [0u8; 1 + WIDTH as usize];
+        payload[0] = DATA;
+        for page in 0..ROWS {
+            self.command(&[0xb0 | page, 0x00, 0x10])?;
+            self.i2c.write(self.address, &payload).map_err(Error::Bus)?;
+        }
+        self.row = 0;
+        self.column = 0;
+        Ok(())
+    }
+
+    fn set_cursor(&mut self, row: u8, column: u8) -> Result<(), Self::Error> {
+        if column >= COLUMNS || row >= ROWS {
+            Err(Error::InvalidCursorPosition { row, column })
+        } else {
+            self.row = row;
+            self.column = column;
+            Ok(())
+        }
+    }
+
+    fn print(&mut self, s: &str) -> Result<(), Self::Error> {
+        for ch in s.chars() {
+            if !font::is_printable(ch) {
+                return Err(Error::UnsupportedCharacter(ch));
+            }
+            let glyph = font::glyph(ch as u8).ok_or(Error::UnsupportedCharacter(ch))?;
+            self.draw(*glyph)?;
+        }
+        Ok(())
+    }
+
+    fn create_custom_char(&mut self, location: u8, char_map: CharMap) -> Result<(), Self::Error> {
+        if location > 7 {
+            Err(Error::InvalidCGRAMLocation(location))
+        } else {
+            self.custom_glyphs[location as usize] = font::from_char_map(&char_map);
+            Ok(())
+        }
+    }
+
+    fn write_custom_char(&mut self, location: u8) -> Result<(), Self::Error> {
+        if location > 7 {
+            Err(Error::InvalidCGRAMLocation(location))
+        } else {
+            self.draw(self.custom_glyphs[location as usize])
+        }
+    }
+
+    /// OLEDs have no backlight, so the whole panel is switched instead.
+    fn set_backlight(&mut self, on: bool) -> Result<(), Self::Error> {
+        self.command(&[if on { 0xaf } else { 0xae }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::hal::mock::{self, Log, Op};
+
+    fn oled(log: &Log) -> Ssd1306<mock::I2c> {
+        let oled = Ssd1306::new(mock::I2c(log.clone()), SSD1306_DEFAULT_ADDRESS).unwrap();
+        log.borrow_mut().clear();
+        oled
+    }
+
+    /// Transfers of single command bytes, one per byte.
+    fn commands(bytes: &[u8]) -> Vec<Op> {
+        bytes.iter().map(|byte| Op::I2c(SSD1306_DEFAULT_ADDRESS, vec![COMMAND, *byte])).collect()
+    }
+
+    fn data(bytes: &[u8]) -> Op {
+        Op::I2c(SSD1306_DEFAULT_ADDRESS, [&[DATA], bytes].concat())
+    }
+
+    #[test]
+    fn init_sets_page_addressing_and_clears_the_panel() {
+        let log = mock::log();
+        Ssd1306::new(mock::I2c(log.clone()), SSD1306_DEFAULT_ADDRESS).unwrap();
+
+        let mut expected = commands(&[
+            0xae, 0xd5, 0x80, 0xa8, 0x3f, 0xd3, 0x00, 0x40, 0x8d, 0x14, 0x20, 0x02, 0xa1, 0xc8, 0xda, 0x12, 0x81, 0xcf,
+            0xd9, 0xf1, 0xdb, 0x40, 0xa4, 0xa6,
+        ]);
+        for page in 0..8 {
+            expected.extend(commands(&[0xb0 | page, 0x00, 0x10]));
+            expected.push(data(&[0; 128]));
+        }
+        expected.extend(commands(&[0xaf]));
+        assert_eq!(*log.borrow(), expected);
+    }
+
+    #[test]
+    fn cells_are_addressed_by_page_and_column() {
+        let log = mock::log();
+        let mut oled = oled(&log);
+        assert_eq!(oled.dimensions(), (8, 21));
+
+        oled.set_cursor(3, 5).unwrap();
+        oled.print("A-").unwrap();
+        let mut expected = commands(&[0xb3, 0x0e, 0x11]); // column 30
+        expected.push(data(&[0x7e, 0x11, 0x11, 0x11, 0x7e, 0x00]));
+        expected.extend(commands(&[0xb3, 0x04, 0x12])); // column 36
+        expected.push(data(&[0x08, 0x08, 0x08, 0x08, 0x08, 0x00]));
+        assert_eq!(*log.borrow(), expected);
+    }
+
+    #[test]
+    fn rows_are_clipped_and_the_cursor_bounded() {
+        let log = mock::log();
+        let mut oled = oled(&log);
+        oled.set_cursor(7, 20).unwrap();
+        oled.print("ab").unwrap();
+        let mut expected = commands(&[0xb7, 0x08, 0x17]); // column 120, the last cell
+        expected.push(data(&[0x20, 0x54, 0x54, 0x54, 0x78, 0x00]));
+        assert_eq!(*log.borrow(), expected);
+
+        assert!(matches!(oled.set_cursor(8, 0), Err(Error::InvalidCursorPosition { row: 8, column: 0 })));
+        assert!(matches!(oled.set_cursor(0, 21), Err(Error::InvalidCursorPosition { row: 0, column: 21 })));
+    }
+
+    #[test]
+    fn unmapped_characters_are_rejected_before_drawing() {
+        let log = mock::log();
+        let mut oled = oled(&log);
+        assert!(matches!(oled.print("é"), Err(Error::UnsupportedCharacter('é'))));
+        assert!(matches!(oled.print("\n"), Err(Error::UnsupportedCharacter('\n'))));
+        assert!(log.borrow().is_empty());
+    }
+
+    #[test]
+    fn custom_characters_are_drawn_from_their_char_map() {
+        let log = mock::log();
+        let mut oled = oled(&log);
+        oled.create_custom_char(7, [0x10, 0, 0, 0, 0, 0, 0, 0x01]).unwrap();
+        assert!(log.borrow().is_empty(), "kept by the driver");
+        oled.write_custom_char(7).unwrap();
+        assert_eq!(log.borrow().last(), Some(&data(&[0x01, 0, 0, 0, 0x80, 0])));
+
+        assert!(matches!(oled.create_custom_char(8, [0; 8]), Err(Error::InvalidCGRAMLocation(8))));
+        assert!(matches!(oled.write_custom_char(8), Err(Error::InvalidCGRAMLocation(8))));
+    }
+
+    #[test]
+    fn backlight_switches_the_panel() {
+        let log = mock::log();
+        let mut oled = oled(&log);
+        oled.set_backlight(false).unwrap();
+        oled.set_backlight(true).unwrap();
+        assert_eq!(*log.borrow(), commands(&[0xae, 0xaf]));
+    }
+}
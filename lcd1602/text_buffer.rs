@@ -0,0 +1,175 @@
+//! In-memory character display, useful to run the UI on a host terminal or to inspect it in tests.
+
+use core::convert::Infallible;
+use core::fmt;
+
+use crate::char_display::CharDisplay;
+use crate::custom_characters::CharMap;
+use crate::font;
+use crate::Error;
+
+/// Character grid of `ROWS` x `COLUMNS` cells, storing codes as the HD44780 DDRAM does
+/// (custom glyphs at [0-7], printable ASCII and the ROM arrows at 0x7e-0x7f otherwise).
+pub struct TextBuffer<const ROWS: usize, const COLUMNS: usize> {
+    cells: [[u8; COLUMNS]; ROWS],
+    custom_chars: [CharMap; 8],
+    row: usize,
+    column: usize,
+    backlight: bool,
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> TextBuffer<ROWS, COLUMNS> {
+    pub const fn new() -> Self {
+        TextBuffer {
+            cells: [[b' '; COLUMNS]; ROWS],
+            custom_chars: [[0; 8]; 8],
+            row: 0,
+            column: 0,
+            backlight: true,
+        }
+    }
+
+    /// Raw codes of a row.
+    pub fn row(&self, row: usize) -> &[u8; COLUMNS] {
+        &self.cells[row]
+    }
+
+    /// Code of a single cell.
+    pub fn cell(&self, row: usize, column: usize) -> u8 {
+        self.cells[row][column]
+    }
+
+    /// Glyph stored at a custom character location.
+    pub fn custom_char(&self, location: u8) -> &CharMap {
+        &self.custom_chars[location as usize]
+    }
+
+    pub fn backlight(&self) -> bool {
+        self.backlight
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.row, self.column)
+    }
+
+    fn put(&mut self, code: u8) {
+        // like the real panel, anything past the end of the row is not visible
+        if self.column < COLUMNS {
+            self.cells[self.row][self.column] = code;
+        }
+        self.column += 1;
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> Default for TextBuffer<ROWS, COLUMNS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const ROWS: usize, const COLUMNS: usize> CharDisplay for TextBuffer<ROWS, COLUMNS> {
+    type Error = Error<Infallible>;
+
+    fn dimensions(&self) -> (u8, u8) {
+        (ROWS as u8, COLUMNS as u8)
+    }
+
+    fn clear(&mut self) -> Result<(), Self::Error> {
+        self.cells = [[b' '; COLUMNS]; ROWS];
+        self.row = 0;
+        self.column = 0;
+        Ok(())
+    }
+
+    fn set_cursor(&mut self, row: u8, column: u8) -> Result<(), Self::Error> {
+        if column as usize >= COLUMNS || row as usize >= ROWS {
+            Err(Error::InvalidCursorPosition { row, column })
+        } else {
+            self.row = row as usize;
+            self.column = column as usize;
+            Ok(())
+        }
+    }
+
+    fn print(&mut self, s: &str) -> Result<(), Self::Error> {
+        for ch in s.chars() {
+            if !font::is_printable(ch) {
+                return Err(Error::UnsupportedCharacter(ch));
+            }
+            self.put(ch as u8);
+        }
+        Ok(())
+    }
+
+    fn create_custom_char(&mut self, location: u8, char_map: CharMap) -> Result<(), Self::Error> {
+        if location > 7 {
+            Err(Error::InvalidCGRAMLocation(location))
+        } else {
+            self.custom_chars[location as usize] = char_map;
+            Ok(())
+        }
+    }
+
+    fn write_custom_char(&mut self, location: u8) -> Result<(), Self::Error> {
+        if location > 7 {
+            Err(Error::InvalidCGRAMLocation(location))
+        } else {
+            self.put(location);
+            Ok(())
+        }
+    }
+
+    fn set_backlight(&mut self, on: bool) -> Result<(), Self::Error> {
+        self.backlight = on;
+        Ok(())
+    }
+}
+
+/// Draw the buffer inside a frame, one line per row, with custom glyphs shown as their location digit
+/// and the ROM arrows as they look on the panel.
+impl<const ROWS: usize, const COLUMNS: usize> fmt::Display for TextBuffer<ROWS, COLUMNS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let border = if self.backlight { '-' } else { '.' };
+        write!(f, "+")?;
+        for _ in 0..COLUMNS { write!(f, "{}", border)?; }
+        writeln!(f, "+")?;
+        for row in self.cells.iter() {
+            write!(f, "|")?;
+            for code in row.iter() {
+                match code {
+                    0..=7 => write!(f, "{}", (b'0' + code) as char)?,
+                    0x7e => write!(f, "→")?,
+                    0x7f => write!(f, "←")?,
+                    _ => write!(f, "{}", *code as char)?,
+                }
+            }
+            writeln!(f, "|")?;
+        }
+        write!(f, "+")?;
+        for _ in 0..COLUMNS { write!(f, "{}", border)?; }
+        write!(f, "+")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::*;
+
+    #[test]
+    fn prints_the_rom_arrows() {
+        let mut buffer = TextBuffer::<1, 4>::new();
+        buffer.print("a\x7e\x7f").unwrap();
+        assert_eq!(buffer.row(0), b"a\x7e\x7f ");
+        assert_eq!(buffer.to_string(), "+----+\n|a→← |\n+----+");
+    }
+
+    #[test]
+    fn rejects_control_and_non_ascii_characters() {
+        let mut buffer = TextBuffer::<1, 4>::new();
+        assert!(matches!(buffer.print("\t"), Err(Error::UnsupportedCharacter('\t'))));
+        assert!(matches!(buffer.print("é"), Err(Error::UnsupportedCharacter('é'))));
+        assert_eq!(buffer.row(0), b"    ");
+    }
+}
@@ -2,7 +2,8 @@
 MEMORY
 {
   /* NOTE K = KiBi = 1024 bytes */
-  FLASH : ORIGIN = 0x08000000, LENGTH = 2M
+  /* The last 256K sector (0x081C0000) is left out, to store the device configuration (see src/config.rs) */
+  FLASH : ORIGIN = 0x08000000, LENGTH = 2M - 256K
   RAM : ORIGIN = 0x20020000, LENGTH = 368K + 16K
   ITCM : ORIGIN = 0x00000000, LENGTH = 16K /* Instruction Tighly Coupled Memory */
   DTCM : ORIGIN = 0x20000000, LENGTH = 128K /* Data Tighly Coupled Memory */
@@ -0,0 +1,98 @@
+//! Button
+//! Debounced pushbutton, detecting short, long and double clicks.
+//!
+//! The detection is the state machine of `timesaver_core::button`, fed with timestamped samples of the button
+//! level; `SharedButton` feeds it from a periodic timer interrupt, pushing the events into an `EventQueue`.
+//! The timer only needs to run while the button is in use: an external interrupt on the press starts it, and
+//! the button stops it again once the click is over.
+
+use core::cell::RefCell;
+use core::ops::DerefMut;
+use cortex_m::interrupt::Mutex;
+use embedded_hal::digital::v2::InputPin;
+use stm32f7xx_hal::gpio::ExtiPin;
+
+pub use timesaver_core::button::{update, ButtonEvent, ButtonState, Timings};
+
+use crate::encoder_interface;
+use crate::events::{EventQueue, InputEvent};
+use crate::millis;
+use crate::sync::free;
+
+/// Active-low pushbutton, with its click detection.
+pub struct Button<PIN> {
+    pin: PIN,
+    timings: Timings,
+    state: ButtonState,
+}
+
+impl<PIN: InputPin> Button<PIN> {
+    pub fn new(pin: PIN, timings: Timings) -> Self {
+        Button { pin, timings, state: ButtonState::new() }
+    }
+
+    /// Sample the pin at `now_ms`, returning the detected event, if any.
+    pub fn sample(&mut self, now_ms: u32) -> Option<ButtonEvent> {
+        let raw = self.pin.is_low().unwrap_or(false);
+        let (state, event) = update(self.state, &self.timings, raw, now_ms);
+        self.state = state;
+        event
+    }
+
+    /// Whether the button is released, and done with its last click.
+    pub fn is_idle(&self) -> bool {
+        self.state.is_idle()
+    }
+}
+
+/// Interrupt-safe handler of a button sampled from a periodic interrupt, meant to be stored in a static.
+pub struct SharedButton<PIN> {
+    button: Mutex<RefCell<Option<Button<PIN>>>>,
+    events: &'static EventQueue,
+}
+
+impl<PIN: InputPin> SharedButton<PIN> {
+    /// Create a handler reporting its events to the given queue.
+    pub const fn new(events: &'static EventQueue) -> Self {
+        SharedButton {
+            button: Mutex::new(RefCell::new(None)),
+            events,
+        }
+    }
+
+    pub fn init(&self, button: Button<PIN>) {
+        free(|cs| {
+            self.button.borrow(cs).replace(Some(button));
+        });
+    }
+
+    /// Handle a periodic sampling tick, pausing the polling timer once the button is idle.
+    pub fn handle_tick(&self) {
+        let now_ms = millis::now().unwrap_or(0);
+        free(|cs| {
+            if let Some(ref mut button) = self.button.borrow(cs).borrow_mut().deref_mut() {
+                if let Some(event) = button.sample(now_ms) {
+                    self.events.push(InputEvent::Button(event));
+                }
+                if button.is_idle() {
+                    encoder_interface::pause_polling();
+                }
+            }
+        });
+    }
+}
+
+impl<PIN: InputPin + ExtiPin> SharedButton<PIN> {
+    /// Handle an External Interrupt on the press of the button, resuming the polling timer to sample it.
+    /// Lines shared with other pins are fine, since nothing happens unless its own pin is pending.
+    pub fn handle_edge(&self) {
+        free(|cs| {
+            if let Some(ref mut button) = self.button.borrow(cs).borrow_mut().deref_mut() {
+                if button.pin.check_interrupt() {
+                    button.pin.clear_interrupt_pending_bit();
+                    encoder_interface::resume_polling();
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,242 @@
+//! Clock
+//! Wall-clock time, kept by the RTC on the LSE crystal (32.768 kHz) in the backup domain, so that it survives
+//! resets (and power losses too, with a backup battery).
+//! Its alarm A rings at a time of day, waking the core up through the RTC_ALARM interrupt.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use stm32f7xx_hal::{interrupt, pac};
+
+pub use timesaver_core::clock::{DateTime, YEARS};
+
+/// Stored in the first backup register once the clock is set, telling it apart from a clock that just started.
+const CLOCK_SET_MAGIC: u32 = 0x7153_0001;
+
+/// Prescalers turning the 32.768 kHz of the LSE into the 1 Hz of the calendar.
+const PREDIV_A: u32 = 127;
+const PREDIV_S: u32 = 255;
+
+/// Set by the alarm interrupt, until taken by `take_alarm`.
+static ALARM_FIRED: AtomicBool = AtomicBool::new(false);
+
+/// External interrupt line the RTC alarms are routed to.
+const EXTI_LINE_RTC_ALARM: u32 = 1 << 17;
+
+/// Status register flags.
+const ISR_INIT: u32 = 1 << 7;
+const ISR_ALRAF: u32 = 1 << 8;
+
+/// Alarm register: ignore the date (only match the time of day).
+const ALRMAR_MSK4: u32 = 1 << 31;
+
+/// RTC write protection keys.
+const RTC_KEY_1: u8 = 0xca;
+const RTC_KEY_2: u8 = 0x53;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Date not existing, or outside of `YEARS`.
+    InvalidDateTime(DateTime),
+}
+
+fn bcd(value: u8) -> u32 {
+    (((value / 10) << 4) | (value % 10)) as u32
+}
+
+fn from_bcd(bcd: u32) -> u8 {
+    (((bcd >> 4) & 0xf) * 10 + (bcd & 0xf)) as u8
+}
+
+/// Time and date registers (24-hour format) holding a date time within `YEARS`.
+pub fn to_registers(date_time: &DateTime) -> (u32, u32) {
+    let tr = (bcd(date_time.hour) << 16) | (bcd(date_time.minute) << 8) | bcd(date_time.second);
+    let dr = (bcd((date_time.year - YEARS.0) as u8) << 16)
+        | ((date_time.weekday() as u32) << 13)
+        | (bcd(date_time.month) << 8)
+        | bcd(date_time.day);
+    (tr, dr)
+}
+
+/// Date time held in time and date registers (24-hour format).
+pub fn from_registers(tr: u32, dr: u32) -> DateTime {
+    DateTime {
+        year: YEARS.0 + from_bcd((dr >> 16) & 0xff) as u16,
+        month: from_bcd((dr >> 8) & 0x1f),
+        day: from_bcd(dr & 0x3f),
+        hour: from_bcd((tr >> 16) & 0x3f),
+        minute: from_bcd((tr >> 8) & 0x7f),
+        second: from_bcd(tr & 0x7f),
+    }
+}
+
+/// Start the RTC on the LSE, unless it is already running from before a reset.
+pub fn init() {
+    let rcc = unsafe { &(*pac::RCC::ptr()) };
+    let pwr = unsafe { &(*pac::PWR::ptr()) };
+
+    // Allow writes to the backup domain
+    rcc.apb1enr.modify(|_, w| w.pwren().set_bit());
+    pwr.cr1.modify(|_, w| w.dbp().set_bit());
+
+    let bdcr = rcc.bdcr.read();
+    if bdcr.rtcen().bit_is_clear() || bdcr.rtcsel().bits() != 0b01 {
+        // First start: reset the backup domain, to be allowed to select the RTC clock
+        rcc.bdcr.modify(|_, w| w.bdrst().set_bit());
+        rcc.bdcr.modify(|_, w| w.bdrst().clear_bit());
+
+        rcc.bdcr.modify(|_, w| w.lseon().set_bit());
+        while rcc.bdcr.read().lserdy().bit_is_clear() {}
+        rcc.bdcr.modify(|_, w| unsafe { w.rtcsel().bits(0b01).rtcen().set_bit() });
+    }
+    wait_for_sync();
+
+    // Alarm interrupts come through the EXTI, on rising edges
+    let exti = unsafe { &(*pac::EXTI::ptr()) };
+    exti.rtsr.modify(|r, w| unsafe { w.bits(r.bits() | EXTI_LINE_RTC_ALARM) });
+    exti.imr.modify(|r, w| unsafe { w.bits(r.bits() | EXTI_LINE_RTC_ALARM) });
+    unsafe { pac::NVIC::unmask(interrupt::RTC_ALARM) };
+}
+
+/// Wait for the calendar registers to be copied into their shadows, which are the ones actually read.
+fn wait_for_sync() {
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+    // RSF is write protected too
+    unlock(rtc);
+    rtc.isr.modify(|_, w| w.rsf().clear_bit());
+    lock(rtc);
+    while rtc.isr.read().rsf().bit_is_clear() {}
+}
+
+/// Whether the clock has been set since the backup domain started.
+pub fn is_set() -> bool {
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+    rtc.bkp0r.read().bits() == CLOCK_SET_MAGIC
+}
+
+/// Current date and time, or None if the clock has never been set.
+pub fn now() -> Option<DateTime> {
+    if !is_set() {
+        return None;
+    }
+
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+    loop {
+        // Reading the time register freezes the date one until it is read, but not the other way round: read
+        // the time again, in case a second has passed meanwhile
+        let tr = rtc.tr.read().bits();
+        let dr = rtc.dr.read().bits();
+        if rtc.tr.read().bits() == tr {
+            return Some(from_registers(tr, dr));
+        }
+    }
+}
+
+/// Busy-wait for the RTC to tick the next second, e.g. to time something against the LSE, whether the clock
+/// is set or not.
+pub fn wait_for_next_second() {
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+    // The date register must be read after the time one, which stays frozen until then
+    let seconds = |rtc: &pac::rtc::RegisterBlock| {
+        let seconds = rtc.tr.read().bits() & 0x7f;
+        rtc.dr.read();
+        seconds
+    };
+    let start = seconds(rtc);
+    while seconds(rtc) == start {}
+}
+
+/// Set date and time, which must be valid and within `YEARS`.
+pub fn set(date_time: &DateTime) -> Result<(), Error> {
+    if !date_time.is_valid() || !(YEARS.0..=YEARS.1).contains(&date_time.year) {
+        return Err(Error::InvalidDateTime(*date_time));
+    }
+    let (tr, dr) = to_registers(date_time);
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+
+    // Disable write protection, and stop the calendar
+    unlock(rtc);
+    rtc.isr.modify(|_, w| w.init().set_bit());
+    while rtc.isr.read().initf().bit_is_clear() {}
+
+    // The synchronous prescaler must be written first
+    rtc.prer.write(|w| unsafe { w.bits(PREDIV_S) });
+    rtc.prer.write(|w| unsafe { w.bits((PREDIV_A << 16) | PREDIV_S) });
+    rtc.tr.write(|w| unsafe { w.bits(tr) });
+    rtc.dr.write(|w| unsafe { w.bits(dr) });
+    rtc.cr.modify(|_, w| w.fmt().clear_bit()); // 24-hour format
+
+    // Restart the calendar, and enable write protection again
+    rtc.isr.modify(|_, w| w.init().clear_bit());
+    lock(rtc);
+    wait_for_sync();
+
+    rtc.bkp0r.write(|w| unsafe { w.bits(CLOCK_SET_MAGIC) });
+    Ok(())
+}
+
+/// Fire an alarm at the next occurrence of the time of day (hours and minutes) of `time`, replacing the one
+/// armed before, if any. The alarm is repeated every day, until cancelled.
+pub fn set_alarm(time: &DateTime) -> Result<(), Error> {
+    if !time.is_valid() {
+        return Err(Error::InvalidDateTime(*time));
+    }
+    let (tr, _) = to_registers(&DateTime { second: 0, ..*time });
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+
+    unlock(rtc);
+    rtc.cr.modify(|_, w| w.alrae().clear_bit());
+    while rtc.isr.read().alrawf().bit_is_clear() {}
+    rtc.alrmar.write(|w| unsafe { w.bits(ALRMAR_MSK4 | tr) });
+    clear_alarm_flag(rtc);
+    ALARM_FIRED.store(false, Ordering::Release);
+    rtc.cr.modify(|_, w| w.alraie().set_bit().alrae().set_bit());
+    lock(rtc);
+    Ok(())
+}
+
+/// Disarm the alarm, forgetting it if already fired.
+pub fn cancel_alarm() {
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+    unlock(rtc);
+    rtc.cr.modify(|_, w| w.alraie().clear_bit().alrae().clear_bit());
+    lock(rtc);
+    ALARM_FIRED.store(false, Ordering::Release);
+}
+
+/// Whether the alarm has fired and not been taken yet.
+pub fn alarm_pending() -> bool {
+    ALARM_FIRED.load(Ordering::Acquire)
+}
+
+/// Take the alarm, returning whether it had fired.
+pub fn take_alarm() -> bool {
+    ALARM_FIRED.swap(false, Ordering::AcqRel)
+}
+
+/// Disable the write protection of the RTC registers.
+fn unlock(rtc: &pac::rtc::RegisterBlock) {
+    rtc.wpr.write(|w| unsafe { w.key().bits(RTC_KEY_1) });
+    rtc.wpr.write(|w| unsafe { w.key().bits(RTC_KEY_2) });
+}
+
+fn lock(rtc: &pac::rtc::RegisterBlock) {
+    rtc.wpr.write(|w| unsafe { w.key().bits(0xff) });
+}
+
+/// Clear the alarm flag alone (the others are cleared writing 0 too, and INIT must stay 0).
+fn clear_alarm_flag(rtc: &pac::rtc::RegisterBlock) {
+    rtc.isr.write(|w| unsafe { w.bits(!(ISR_ALRAF | ISR_INIT)) });
+}
+
+#[interrupt]
+fn RTC_ALARM() {
+    let rtc = unsafe { &(*pac::RTC::ptr()) };
+    if rtc.isr.read().bits() & ISR_ALRAF != 0 {
+        clear_alarm_flag(rtc);
+        ALARM_FIRED.store(true, Ordering::Release);
+    }
+
+    // Clear the pending line, or the interrupt would fire again straight away
+    let exti = unsafe { &(*pac::EXTI::ptr()) };
+    exti.pr.write(|w| unsafe { w.bits(EXTI_LINE_RTC_ALARM) });
+}
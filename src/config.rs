@@ -0,0 +1,92 @@
+//! Config
+//! Device configuration, persisted in the last sector of the internal flash (kept out of FLASH in memory.x), in
+//! the layout of `timesaver_core::config`.
+
+use core::ptr;
+
+use stm32f7xx_hal::pac;
+
+pub use timesaver_core::config::{decode, encode, DeviceConfig, CONFIG_LEN};
+
+/// Sector 11 of the 2 MB flash, in single bank mode.
+const CONFIG_SECTOR: u8 = 11;
+const CONFIG_ADDRESS: usize = 0x081C_0000;
+
+/// Flash register keys, unlocking the control register.
+const FLASH_KEY_1: u32 = 0x4567_0123;
+const FLASH_KEY_2: u32 = 0xcdef_89ab;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Flash operation failed, with the content of the status register.
+    Flash(u32),
+}
+
+/// Read the stored configuration, falling back to the default one if none is valid.
+pub fn load() -> DeviceConfig {
+    let mut bytes = [0u8; CONFIG_LEN];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        // the flash is memory mapped, and the sector is never used by the firmware
+        *byte = unsafe { ptr::read_volatile((CONFIG_ADDRESS + i) as *const u8) };
+    }
+    decode(&bytes).unwrap_or_default()
+}
+
+/// Erase the configuration sector and write the given configuration into it.
+/// The CPU stalls while erasing, which may take a few seconds.
+pub fn store(flash: &mut pac::FLASH, config: &DeviceConfig) -> Result<(), Error> {
+    // Unlock the control register
+    if flash.cr.read().lock().bit_is_set() {
+        flash.keyr.write(|w| unsafe { w.bits(FLASH_KEY_1) });
+        flash.keyr.write(|w| unsafe { w.bits(FLASH_KEY_2) });
+    }
+
+    let result = erase_sector(flash).and_then(|_| program(flash, &encode(config)));
+
+    // Lock it again, whatever the result
+    flash.cr.modify(|_, w| w.lock().set_bit());
+    result
+}
+
+/// Wait for the current operation to complete, checking for its errors.
+fn wait(flash: &pac::FLASH) -> Result<(), Error> {
+    while flash.sr.read().bsy().bit_is_set() {}
+
+    let sr = flash.sr.read();
+    if sr.operr().bit_is_set()
+        || sr.wrperr().bit_is_set()
+        || sr.pgaerr().bit_is_set()
+        || sr.pgperr().bit_is_set()
+        || sr.erserr().bit_is_set()
+    {
+        let bits = sr.bits();
+        flash.sr.write(|w| unsafe { w.bits(bits) }); // error flags are cleared writing 1
+        return Err(Error::Flash(bits));
+    }
+    Ok(())
+}
+
+fn erase_sector(flash: &mut pac::FLASH) -> Result<(), Error> {
+    wait(flash)?;
+    flash.cr.modify(|_, w| unsafe { w.psize().bits(0b10).ser().set_bit().snb().bits(CONFIG_SECTOR) }); // 32-bit
+    flash.cr.modify(|_, w| w.strt().set_bit());
+    let result = wait(flash);
+    flash.cr.modify(|_, w| w.ser().clear_bit());
+    result
+}
+
+fn program(flash: &mut pac::FLASH, bytes: &[u8]) -> Result<(), Error> {
+    wait(flash)?;
+    flash.cr.modify(|_, w| unsafe { w.psize().bits(0b00).pg().set_bit() }); // 8-bit, no alignment needed
+    let mut result = Ok(());
+    for (i, byte) in bytes.iter().enumerate() {
+        unsafe { ptr::write_volatile((CONFIG_ADDRESS + i) as *mut u8, *byte) };
+        cortex_m::asm::dsb();
+        result = wait(flash);
+        if result.is_err() {
+            break;
+        }
+    }
+    flash.cr.modify(|_, w| w.pg().clear_bit());
+    result
+}
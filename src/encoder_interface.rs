@@ -1,80 +1,441 @@
+//! Encoder interface
+//! Rotary encoders decoded either in software, sampling their pins on external interrupts or periodically
+//! from a timer, or in hardware, through a timer in encoder mode. All of them share the same get/set/reset API.
+//!
+//! Each encoder lives in its own static (`SharedEncoder` or `SharedQei`), so that any number of them can be
+//! used at once, and the interrupt handlers feeding the software ones are generated with `encoder_interrupts!`.
+//! Created `with_events`, they also report every change as a `Rotate` event.
+//!
+//! Software-decoded values are atomics, read without entering a critical section; hardware-decoded ones need
+//! one to collect the counts of the timer first.
+
 use core::cell::{Cell, RefCell};
 use core::ops::DerefMut;
-use cortex_m::interrupt::{free, Mutex};
-use rotary_encoder_embedded::{standard::StandardMode, Direction, RotaryEncoder};
-use stm32f7xx_hal::gpio::{ExtiPin, Input, Pin, PullUp};
-
-/// Interrupt-safe Encoder handler.
-pub static ROTARY_ENCODER: Mutex<
-    RefCell<
-        Option<RotaryEncoder<StandardMode, Pin<'B', 1, Input<PullUp>>, Pin<'B', 5, Input<PullUp>>>>,
-    >,
-> = Mutex::new(RefCell::new(None));
-
-static ENCODER_VALUE: Mutex<Cell<i32>> = Mutex::new(Cell::new(0i32));
-
-/// Initialize memory-safe encoder handler for DT -> PB1 and CLK -> PB5.
-pub fn init_encoder(dt: Pin<'B', 1, Input<PullUp>>, clk: Pin<'B', 5, Input<PullUp>>) {
-    // Encoder setup
-    free(|cs| {
-        ROTARY_ENCODER
-            .borrow(cs)
-            .replace(Some(RotaryEncoder::new(dt, clk).into_standard_mode()));
-    });
+use core::sync::atomic::{AtomicI32, Ordering};
+use cortex_m::interrupt::{CriticalSection, Mutex};
+use embedded_hal::digital::v2::InputPin;
+use stm32f7xx_hal::gpio::ExtiPin;
+use stm32f7xx_hal::pac::TIM7;
+use stm32f7xx_hal::timer::{CounterHz, Event};
+use stm32f7xx_hal::{interrupt, pac, prelude::*};
+
+use acceleration::{AccelerationState, Curve};
+use decoder::{DecoderState, EncoderConfig};
+use qei::Qei;
+use range::Policy;
+
+use crate::events::{EventQueue, InputEvent};
+use crate::millis;
+use crate::sync::free;
+
+pub use timesaver_core::encoder_interface::{acceleration, decoder, range};
+
+pub mod qei;
+
+/// Common API of all the encoder backends.
+pub trait EncoderValue {
+    /// Get current encoder value.
+    fn get(&self) -> i32;
+
+    /// Set encoder internal value to desired value, brought inside the current range.
+    fn set(&self, v: i32);
+
+    /// Change range and step size, e.g. when the knob moves to a different context.
+    /// The current value is brought inside the new range.
+    fn set_policy(&self, policy: Policy);
+
+    /// Change resolution and direction of the decoding, e.g. when the device configuration changes.
+    /// The current value is kept.
+    fn set_config(&self, config: EncoderConfig);
+
+    /// Reset encoder value to 0.
+    fn reset(&self) {
+        self.set(0);
+    }
+}
+
+/// Rotary encoder on any pair of input pins, with the status of their decoding.
+pub struct Encoder<DT, CLK> {
+    dt: DT,
+    clk: CLK,
+    config: EncoderConfig,
+    state: DecoderState,
+    curve: Curve,
+    acceleration: AccelerationState,
 }
 
-/// Get current encoder value.
-pub fn get() -> i32 {
-    free(|cs| ENCODER_VALUE.borrow(cs).get())
+impl<DT: InputPin, CLK: InputPin> Encoder<DT, CLK> {
+    /// Create a full-step encoder without acceleration.
+    pub fn new(dt: DT, clk: CLK) -> Self {
+        Encoder {
+            dt,
+            clk,
+            config: EncoderConfig::DEFAULT,
+            state: DecoderState::new(),
+            curve: Curve::Linear,
+            acceleration: AccelerationState::new(),
+        }
+    }
+
+    /// Change resolution and direction of the decoding.
+    pub fn with_config(mut self, config: EncoderConfig) -> Self {
+        self.config = config;
+        self.state = DecoderState::new();
+        self
+    }
+
+    /// Sample the pins, returning the (accelerated) steps completed since the previous sample.
+    fn sample(&mut self) -> i32 {
+        let dt = self.dt.is_high().unwrap_or(false);
+        let clk = self.clk.is_high().unwrap_or(false);
+        let direction = self.state.update(self.config.resolution, dt, clk);
+        if direction == 0 {
+            return 0;
+        }
+        let direction = self.config.orient(direction as i32) as i8;
+
+        let now_ms = millis::now().unwrap_or(0);
+        let steps_per_revolution = self.config.steps_per_revolution();
+        let (acceleration, step) =
+            acceleration::accelerate(self.acceleration, &self.curve, steps_per_revolution, now_ms, direction);
+        self.acceleration = acceleration;
+        step
+    }
+
+    /// Give back the owned pins.
+    pub fn release(self) -> (DT, CLK) {
+        (self.dt, self.clk)
+    }
 }
 
-/// Set encoder internal value to desired value.
-pub fn set(v: i32) {
-    free(|cs| ENCODER_VALUE.borrow(cs).replace(v));
+/// Interrupt-safe handler of a software-decoded encoder, meant to be stored in a static.
+pub struct SharedEncoder<DT, CLK> {
+    encoder: Mutex<RefCell<Option<Encoder<DT, CLK>>>>,
+    /// Only changed in critical sections, so that updates are not lost, but read anywhere.
+    value: AtomicI32,
+    policy: Mutex<Cell<Policy>>,
+    events: Option<&'static EventQueue>,
 }
 
-/// Reset encoder value to 0.
-pub fn reset() {
-    set(0);
+impl<DT, CLK> SharedEncoder<DT, CLK>
+where
+    DT: InputPin + ExtiPin,
+    CLK: InputPin + ExtiPin,
+{
+    pub const fn new() -> Self {
+        SharedEncoder {
+            encoder: Mutex::new(RefCell::new(None)),
+            value: AtomicI32::new(0),
+            policy: Mutex::new(Cell::new(Policy::UNBOUNDED)),
+            events: None,
+        }
+    }
+
+    /// Create a handler also reporting its rotations to the given queue.
+    pub const fn with_events(events: &'static EventQueue) -> Self {
+        SharedEncoder {
+            encoder: Mutex::new(RefCell::new(None)),
+            value: AtomicI32::new(0),
+            policy: Mutex::new(Cell::new(Policy::UNBOUNDED)),
+            events: Some(events),
+        }
+    }
+
+    /// Take ownership of the encoder, which will be fed by the interrupt handlers it is registered with.
+    pub fn init(&self, encoder: Encoder<DT, CLK>) {
+        free(|cs| {
+            self.encoder.borrow(cs).replace(Some(encoder));
+        });
+    }
+
+    /// Change the acceleration curve, e.g. when the knob moves to a different context.
+    pub fn set_acceleration(&self, curve: Curve) {
+        free(|cs| {
+            if let Some(ref mut encoder) = self.encoder.borrow(cs).borrow_mut().deref_mut() {
+                encoder.curve = curve;
+                encoder.acceleration = AccelerationState::new();
+            }
+        });
+    }
+
+    /// Sample the encoder and update its value with the completed steps.
+    fn update_value(&self, cs: &CriticalSection, encoder: &mut Encoder<DT, CLK>) {
+        let steps = encoder.sample();
+        if steps != 0 {
+            let value = range::apply(&self.policy.borrow(cs).get(), self.value.load(Ordering::Relaxed), steps);
+            self.value.store(value, Ordering::Relaxed);
+            if let Some(events) = self.events {
+                events.push(InputEvent::Rotate(steps));
+            }
+        }
+    }
+
+    /// Handle an External Interrupt (rising and falling edges on DT and CLK pins).
+    /// Lines shared with other pins are fine, since nothing happens unless one of its own pins is pending.
+    pub fn handle_edge(&self) {
+        free(|cs| {
+            if let Some(ref mut encoder) = self.encoder.borrow(cs).borrow_mut().deref_mut() {
+                let mut pending = false;
+                if encoder.dt.check_interrupt() {
+                    encoder.dt.clear_interrupt_pending_bit();
+                    pending = true;
+                }
+                if encoder.clk.check_interrupt() {
+                    encoder.clk.clear_interrupt_pending_bit();
+                    pending = true;
+                }
+
+                if pending {
+                    self.update_value(cs, encoder);
+                }
+            }
+        });
+    }
+
+    /// Handle a periodic sampling tick.
+    pub fn handle_tick(&self) {
+        free(|cs| {
+            if let Some(ref mut encoder) = self.encoder.borrow(cs).borrow_mut().deref_mut() {
+                self.update_value(cs, encoder);
+            }
+        });
+    }
 }
 
-/// Interrupted pin, used for interrupt resets.
-pub enum InterruptedPin {
-    DtPin,
-    ClkPin,
+impl<DT, CLK> EncoderValue for SharedEncoder<DT, CLK> {
+    fn get(&self) -> i32 {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, v: i32) {
+        free(|cs| {
+            let policy = self.policy.borrow(cs).get();
+            self.value.store(range::fit(&policy.range, v), Ordering::Relaxed);
+        });
+    }
+
+    fn set_policy(&self, policy: Policy) {
+        free(|cs| {
+            self.policy.borrow(cs).replace(policy);
+            let value = range::fit(&policy.range, self.value.load(Ordering::Relaxed));
+            self.value.store(value, Ordering::Relaxed);
+        });
+    }
+
+    fn set_config(&self, config: EncoderConfig) {
+        free(|cs| {
+            if let Some(ref mut encoder) = self.encoder.borrow(cs).borrow_mut().deref_mut() {
+                encoder.config = config;
+                encoder.state = DecoderState::new();
+                encoder.acceleration = AccelerationState::new();
+            }
+        });
+    }
 }
 
-/// Update current encoder values during External Interrupt (rising and falling edges on DT and CLK pins).
-pub fn handle_encoder_interrupt(interrupted_pin: InterruptedPin) {
-    // Retrieve Rotary Encoder from safely stored static global
-    free(|cs| {
-        if let Some(ref mut rotary_encoder) = ROTARY_ENCODER.borrow(cs).borrow_mut().deref_mut() {
-            // Borrow the pins to clear the pending interrupt bit
-            let (dt, clk) = rotary_encoder.pins_mut();
-            match interrupted_pin {
-                InterruptedPin::DtPin => {
-                    dt.clear_interrupt_pending_bit();
+/// Interrupt-safe handler of a hardware-decoded encoder, meant to be stored in a static.
+/// Detents are not timestamped by the timer, so it does not support acceleration.
+pub struct SharedQei<TIM, PINS> {
+    qei: Mutex<RefCell<Option<Qei<TIM, PINS>>>>,
+    /// Detents counted by the timer when `value` was last updated.
+    last_detents: Mutex<Cell<i32>>,
+    value: Mutex<Cell<i32>>,
+    policy: Mutex<Cell<Policy>>,
+    events: Option<&'static EventQueue>,
+}
+
+impl<TIM, PINS> SharedQei<TIM, PINS> {
+    pub const fn new() -> Self {
+        SharedQei {
+            qei: Mutex::new(RefCell::new(None)),
+            last_detents: Mutex::new(Cell::new(0i32)),
+            value: Mutex::new(Cell::new(0i32)),
+            policy: Mutex::new(Cell::new(Policy::UNBOUNDED)),
+            events: None,
+        }
+    }
+
+    /// Create a handler also reporting its rotations to the given queue.
+    /// The timer raises no interrupt on detents: rotations are only noticed when the value is read, or on the
+    /// ticks of the polling timer if registered as `polling` in `encoder_interrupts!`.
+    pub const fn with_events(events: &'static EventQueue) -> Self {
+        SharedQei {
+            qei: Mutex::new(RefCell::new(None)),
+            last_detents: Mutex::new(Cell::new(0i32)),
+            value: Mutex::new(Cell::new(0i32)),
+            policy: Mutex::new(Cell::new(Policy::UNBOUNDED)),
+            events: Some(events),
+        }
+    }
+}
+
+macro_rules! shared_qei {
+    ($($TIM:ident),+) => {
+        $(
+            impl<PINS: qei::QeiPins<pac::$TIM>> SharedQei<pac::$TIM, PINS> {
+                pub fn init(&self, mut qei: Qei<pac::$TIM, PINS>) {
+                    free(|cs| {
+                        self.last_detents.borrow(cs).replace(qei.get());
+                        self.qei.borrow(cs).replace(Some(qei));
+                    });
                 }
-                InterruptedPin::ClkPin => {
-                    clk.clear_interrupt_pending_bit();
+
+                /// Apply the detents counted by the timer since the last update to the value.
+                fn update_value(&self, cs: &CriticalSection) {
+                    if let Some(qei) = self.qei.borrow(cs).borrow_mut().deref_mut() {
+                        let detents = qei.get();
+                        let steps = detents.wrapping_sub(self.last_detents.borrow(cs).replace(detents));
+                        if steps != 0 {
+                            let cell = self.value.borrow(cs);
+                            cell.replace(range::apply(&self.policy.borrow(cs).get(), cell.get(), steps));
+                            if let Some(events) = self.events {
+                                events.push(InputEvent::Rotate(steps));
+                            }
+                        }
+                    }
+                }
+
+                /// Handle a periodic sampling tick, noticing the rotations counted by the timer meanwhile.
+                pub fn handle_tick(&self) {
+                    free(|cs| self.update_value(cs));
                 }
             }
 
-            // Update the encoder, which will compute its direction
-            rotary_encoder.update();
-            match rotary_encoder.direction() {
-                Direction::Clockwise => {
-                    let cell = ENCODER_VALUE.borrow(cs);
-                    cell.replace(cell.get() + 1);
+            impl<PINS: qei::QeiPins<pac::$TIM>> EncoderValue for SharedQei<pac::$TIM, PINS> {
+                fn get(&self) -> i32 {
+                    free(|cs| {
+                        self.update_value(cs);
+                        self.value.borrow(cs).get()
+                    })
                 }
-                Direction::Anticlockwise => {
-                    let cell = ENCODER_VALUE.borrow(cs);
-                    cell.replace(cell.get() - 1);
+
+                fn set(&self, v: i32) {
+                    free(|cs| {
+                        self.update_value(cs);
+                        let policy = self.policy.borrow(cs).get();
+                        self.value.borrow(cs).replace(range::fit(&policy.range, v));
+                    });
                 }
-                Direction::None => {
-                    // Do nothing
+
+                fn set_policy(&self, policy: Policy) {
+                    free(|cs| {
+                        self.update_value(cs);
+                        self.policy.borrow(cs).replace(policy);
+                        let cell = self.value.borrow(cs);
+                        cell.replace(range::fit(&policy.range, cell.get()));
+                    });
+                }
+
+                fn set_config(&self, config: EncoderConfig) {
+                    free(|cs| {
+                        self.update_value(cs);
+                        if let Some(qei) = self.qei.borrow(cs).borrow_mut().deref_mut() {
+                            qei.set_config(config);
+                            self.last_detents.borrow(cs).replace(qei.get());
+                        }
+                    });
                 }
             }
+        )+
+    };
+}
+
+shared_qei!(TIM3, TIM4);
+
+/// Timer sampling the inputs registered as `polling` in `encoder_interrupts!`, with its rate.
+struct PollingTimer {
+    timer: CounterHz<TIM7>,
+    rate_hz: u32,
+    /// Whether it keeps running, or only while some input asks for it (see `resume_polling`).
+    continuous: bool,
+    running: bool,
+}
+
+static POLLING_TIMER: Mutex<RefCell<Option<PollingTimer>>> = Mutex::new(RefCell::new(None));
+
+/// Start sampling polled inputs from TIM7 update interrupt, at the given rate in Hz.
+/// Compared to external interrupts, a bouncing contact cannot flood the CPU with interrupts.
+///
+/// Unless `continuous`, the timer waits for `resume_polling` to start, and stops again on `pause_polling`: this
+/// way it does not wake the core up while the polled inputs are idle (e.g. a button only sampled once pressed).
+pub fn start_polling(mut timer: CounterHz<TIM7>, rate_hz: u32, continuous: bool) {
+    if continuous {
+        timer.start(rate_hz.Hz()).unwrap();
+    }
+    timer.listen(Event::Update);
+    free(|cs| {
+        let polling = PollingTimer { timer, rate_hz, continuous, running: continuous };
+        POLLING_TIMER.borrow(cs).replace(Some(polling));
+    });
+
+    // Enable timer interrupts
+    unsafe { pac::NVIC::unmask(interrupt::TIM7) };
+}
+
+/// Start the polling timer, if it is stopped.
+pub fn resume_polling() {
+    free(|cs| {
+        if let Some(polling) = POLLING_TIMER.borrow(cs).borrow_mut().deref_mut() {
+            // Restarting a running timer would just delay its next tick
+            if !polling.running {
+                polling.timer.start(polling.rate_hz.Hz()).unwrap();
+                polling.running = true;
+            }
         }
     });
 }
+
+/// Stop the polling timer until `resume_polling`, unless it is continuous.
+pub fn pause_polling() {
+    free(|cs| {
+        if let Some(polling) = POLLING_TIMER.borrow(cs).borrow_mut().deref_mut() {
+            if polling.running && !polling.continuous {
+                polling.timer.cancel().unwrap();
+                polling.running = false;
+            }
+        }
+    });
+}
+
+/// Clear the pending interrupt of the polling timer.
+pub fn clear_polling_interrupt() {
+    free(|cs| {
+        if let Some(polling) = POLLING_TIMER.borrow(cs).borrow_mut().deref_mut() {
+            polling.timer.clear_interrupt(Event::Update);
+        }
+    });
+}
+
+/// Generate the interrupt handlers feeding software-decoded encoders (`SharedEncoder` statics):
+/// - `exti LINE => [ENCODERS]` samples the encoders on the edges notified by an EXTI line;
+/// - `polling TIM7 => [ENCODERS]` samples the encoders on every tick of the polling timer.
+///
+/// Any other static with the same `handle_edge`/`handle_tick` methods can be listed too, e.g. a `SharedButton`.
+///
+/// `stm32f7xx_hal::interrupt` must be in scope where the macro is expanded.
+/// ```ignore
+/// encoder_interrupts! {
+///     exti EXTI1 => [ZONE_1];
+///     exti EXTI9_5 => [ZONE_1, ZONE_2];
+///     polling TIM7 => [ZONE_3];
+/// }
+/// ```
+macro_rules! encoder_interrupts {
+    ($($kind:ident $irq:ident => [$($encoder:expr),+ $(,)?]);+ $(;)?) => {
+        $(
+            #[interrupt]
+            fn $irq() {
+                $crate::encoder_interface::encoder_interrupts!(@handle $kind [$($encoder),+]);
+            }
+        )+
+    };
+    (@handle exti [$($encoder:expr),+]) => {
+        $( $encoder.handle_edge(); )+
+    };
+    (@handle polling [$($encoder:expr),+]) => {
+        $crate::encoder_interface::clear_polling_interrupt();
+        $( $encoder.handle_tick(); )+
+    };
+}
+
+pub(crate) use encoder_interrupts;
@@ -0,0 +1,102 @@
+//! Hardware quadrature decoding, using a general-purpose timer in encoder interface mode.
+//! The timer counts every edge of both channels by itself, so no interrupt is needed to follow the knob.
+
+use stm32f7xx_hal::gpio::{Alternate, Pin};
+use stm32f7xx_hal::pac::{self, TIM3, TIM4};
+
+use super::decoder::EncoderConfig;
+
+/// Counts produced by a full quadrature cycle (both edges of both channels).
+const COUNTS_PER_CYCLE: i32 = 4;
+
+/// Pins that can be routed to channels 1 and 2 of a timer, as (DT, CLK).
+pub trait QeiPins<TIM> {}
+
+impl QeiPins<TIM3> for (Pin<'C', 6, Alternate<2>>, Pin<'C', 7, Alternate<2>>) {}
+impl QeiPins<TIM4> for (Pin<'D', 12, Alternate<2>>, Pin<'D', 13, Alternate<2>>) {}
+
+/// Quadrature Encoder Interface on a 16-bit timer, extended to 32-bit in software.
+pub struct Qei<TIM, PINS> {
+    tim: TIM,
+    pins: PINS,
+    config: EncoderConfig,
+    last_count: u16,
+    position: i32,
+}
+
+macro_rules! qei {
+    ($TIM:ident, $timXen:ident) => {
+        impl<PINS: QeiPins<$TIM>> Qei<$TIM, PINS> {
+            /// Configure the timer in encoder mode 3, with the given input filter ([0-15], see `ICxF` bits).
+            pub fn new(tim: $TIM, pins: PINS, filter: u8) -> Self {
+                // Enable timer clock
+                let rcc = unsafe { &(*pac::RCC::ptr()) };
+                rcc.apb1enr.modify(|_, w| w.$timXen().set_bit());
+
+                tim.cr1.modify(|_, w| w.cen().clear_bit());
+                tim.ccmr1_input().write(|w| unsafe {
+                    w.cc1s().bits(0b01) // IC1 mapped on TI1
+                        .ic1f().bits(filter & 0x0f)
+                        .cc2s().bits(0b01) // IC2 mapped on TI2
+                        .ic2f().bits(filter & 0x0f)
+                });
+                tim.ccer.write(|w| w.cc1p().clear_bit().cc2p().clear_bit()); // rising polarity, no inversion
+                tim.smcr.write(|w| unsafe { w.sms().bits(0b011) }); // count on both TI1 and TI2 edges
+                tim.arr.write(|w| unsafe { w.bits(0xffff) });
+                tim.cnt.write(|w| unsafe { w.bits(0) });
+                tim.cr1.modify(|_, w| w.cen().set_bit());
+
+                Qei { tim, pins, config: EncoderConfig::DEFAULT, last_count: 0, position: 0 }
+            }
+
+            /// Change resolution and direction of the decoding.
+            pub fn with_config(mut self, config: EncoderConfig) -> Self {
+                self.config = config;
+                self
+            }
+
+            /// Change resolution and direction of the decoding, keeping the current value.
+            pub fn set_config(&mut self, config: EncoderConfig) {
+                let value = self.get();
+                self.config = config;
+                self.set(value);
+            }
+
+            /// Counts making a step, at the configured resolution.
+            fn counts_per_step(&self) -> i32 {
+                COUNTS_PER_CYCLE / self.config.resolution.steps_per_cycle() as i32
+            }
+
+            /// Accumulate the counts seen since the last call.
+            /// It must be called before the 16-bit counter moves by more than half its range.
+            fn update(&mut self) {
+                let count = self.tim.cnt.read().bits() as u16;
+                self.position = self.position.wrapping_add(count.wrapping_sub(self.last_count) as i16 as i32);
+                self.last_count = count;
+            }
+
+            /// Get current value, in steps.
+            pub fn get(&mut self) -> i32 {
+                self.update();
+                // round to the closest step, so that both directions switch half-way
+                let counts_per_step = self.counts_per_step();
+                self.config.orient((self.position + counts_per_step / 2).div_euclid(counts_per_step))
+            }
+
+            /// Set current value, in steps.
+            pub fn set(&mut self, v: i32) {
+                self.update();
+                self.position = self.config.orient(v).wrapping_mul(self.counts_per_step());
+            }
+
+            /// Stop the timer and give back its resources.
+            pub fn release(self) -> ($TIM, PINS) {
+                self.tim.cr1.modify(|_, w| w.cen().clear_bit());
+                (self.tim, self.pins)
+            }
+        }
+    };
+}
+
+qei!(TIM3, tim3en);
+qei!(TIM4, tim4en);
@@ -0,0 +1,69 @@
+//! Events
+//! Input events (knob rotations and button clicks) delivered from interrupts to the main loop, in order.
+
+use timesaver_core::events::Queue;
+
+use crate::button::ButtonEvent;
+use crate::millis::{self, Instant};
+use crate::sync::Interrupts;
+
+/// Events an `EventQueue` can hold before dropping new ones.
+pub const EVENT_QUEUE_CAPACITY: usize = 16;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum InputEvent {
+    /// Knob turned by the given steps: positive clockwise, negative anticlockwise.
+    Rotate(i32),
+    Button(ButtonEvent),
+}
+
+/// Input event, with the time it was detected at.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct TimedEvent {
+    pub event: InputEvent,
+    pub at: Instant,
+}
+
+/// Interrupt-safe, fixed-capacity FIFO of input events, meant to be stored in a static.
+/// Any interrupt can push into it, while the main loop consumes it.
+/// Checking for events, or for dropped ones, does not enter a critical section when there are none.
+pub struct EventQueue {
+    events: Queue<TimedEvent, Interrupts, EVENT_QUEUE_CAPACITY>,
+}
+
+impl EventQueue {
+    pub const fn new() -> Self {
+        EventQueue { events: Queue::new() }
+    }
+
+    /// Append an event, timestamped now. If the queue is full the event is dropped and accounted for,
+    /// keeping the older ones so that the consumer still sees them in order.
+    pub fn push(&self, event: InputEvent) {
+        let at = millis::now_instant().unwrap_or(Instant::ZERO);
+        self.events.push(TimedEvent { event, at });
+    }
+
+    /// Take the oldest event, if any.
+    pub fn pop(&self) -> Option<TimedEvent> {
+        self.events.pop()
+    }
+
+    /// Number of events waiting to be consumed.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Discard all waiting events, e.g. clicks queued while a long operation was running.
+    pub fn clear(&self) {
+        self.events.clear()
+    }
+
+    /// Events dropped so far because the queue was full.
+    pub fn dropped(&self) -> u32 {
+        self.events.dropped()
+    }
+}
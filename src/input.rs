@@ -0,0 +1,77 @@
+//! Input
+//! High-level UI events, and the input sources producing them from any kind of front panel.
+//!
+//! The TimeSaver only reacts to `UiEvent`s, so the same firmware works with a rotary encoder, a keypad, a pair
+//! of buttons or commands sent over a serial link (e.g. to drive it remotely in tests).
+
+use embedded_hal::digital::v2::InputPin;
+
+pub use timesaver_core::input::{from_click, ButtonPair, Knob, UiEvent};
+
+use crate::button::Timings;
+use crate::events::{EventQueue, InputEvent};
+use crate::millis::Instant;
+
+pub mod commands;
+pub mod keypad;
+
+/// Anything producing UI events.
+pub trait InputSource {
+    /// Next UI event, if any, without blocking, with the time its input was detected at. `now` times the sources
+    /// sampled on polling.
+    fn poll(&mut self, now: Instant) -> Option<(UiEvent, Instant)>;
+}
+
+/// Rotary encoder with pushbutton, read from the queue its interrupts report to, and mapped as a `Knob`
+/// (all the steps of a rotation at once).
+pub struct KnobInput {
+    events: &'static EventQueue,
+    knob: Knob,
+}
+
+impl KnobInput {
+    pub fn new(events: &'static EventQueue) -> Self {
+        KnobInput { events, knob: Knob::new() }
+    }
+}
+
+impl InputSource for KnobInput {
+    fn poll(&mut self, _now: Instant) -> Option<(UiEvent, Instant)> {
+        loop {
+            let timed = self.events.pop()?;
+            let event = match timed.event {
+                InputEvent::Rotate(steps) => self.knob.turn(steps),
+                InputEvent::Button(click) => self.knob.click(click),
+            };
+            if let Some(event) = event {
+                return Some((event, timed.at));
+            }
+        }
+    }
+}
+
+/// Pair of active-low buttons moving Up and Down, repeating while held, sampled on every poll.
+pub struct UpDownButtons<UP, DOWN> {
+    up: UP,
+    down: DOWN,
+    pair: ButtonPair,
+}
+
+impl<UP: InputPin, DOWN: InputPin> UpDownButtons<UP, DOWN> {
+    pub fn new(up: UP, down: DOWN, timings: Timings) -> Self {
+        UpDownButtons { up, down, pair: ButtonPair::new(timings) }
+    }
+
+    /// Give back the owned pins.
+    pub fn release(self) -> (UP, DOWN) {
+        (self.up, self.down)
+    }
+}
+
+impl<UP: InputPin, DOWN: InputPin> InputSource for UpDownButtons<UP, DOWN> {
+    fn poll(&mut self, now: Instant) -> Option<(UiEvent, Instant)> {
+        let up = self.up.is_low().unwrap_or(false);
+        let down = self.down.is_low().unwrap_or(false);
+        self.pair.update(up, down, now.as_millis() as u32).map(|event| (event, now))
+    }
+}
@@ -0,0 +1,74 @@
+//! Text commands received over a serial link (RTT or UART), one per line (see `timesaver_core::input::commands`).
+
+use embedded_hal::serial::Read;
+use rtt_target::DownChannel;
+
+use timesaver_core::input::commands::{CommandParser, Line};
+
+pub use timesaver_core::input::commands::Command;
+
+use super::{InputSource, UiEvent};
+use crate::millis::Instant;
+
+/// Anything providing the received bytes, one at a time and without blocking.
+pub trait ByteSource {
+    fn read_byte(&mut self) -> Option<u8>;
+}
+
+impl ByteSource for DownChannel {
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut byte = [0u8];
+        match self.read(&mut byte) {
+            1 => Some(byte[0]),
+            _ => None,
+        }
+    }
+}
+
+/// UART receiver (any `embedded_hal::serial::Read` implementation), as a byte source.
+pub struct Serial<RX>(pub RX);
+
+impl<RX: Read<u8>> ByteSource for Serial<RX> {
+    fn read_byte(&mut self) -> Option<u8> {
+        // Framing and overrun errors just lose the byte, and the command it belongs to will be unknown
+        self.0.read().ok()
+    }
+}
+
+/// Commands read from any byte source: UI events are polled as from any input source, while device commands
+/// are left for the firmware to take, in the order they were received.
+pub struct TextCommands<S> {
+    source: S,
+    parser: CommandParser,
+    /// Device command received and not taken yet, which stops reading until it is.
+    command: Option<Command>,
+}
+
+impl<S: ByteSource> TextCommands<S> {
+    pub fn new(source: S) -> Self {
+        TextCommands { source, parser: CommandParser::new(), command: None }
+    }
+
+    /// Take the device command received last, if any.
+    pub fn take_command(&mut self) -> Option<Command> {
+        self.command.take()
+    }
+
+    /// Whether a command is partly received, the rest of it being expected soon.
+    pub fn is_receiving(&self) -> bool {
+        self.parser.is_partial()
+    }
+}
+
+impl<S: ByteSource> InputSource for TextCommands<S> {
+    fn poll(&mut self, now: Instant) -> Option<(UiEvent, Instant)> {
+        while self.command.is_none() {
+            match self.parser.feed(self.source.read_byte()?) {
+                Some(Line::Ui(event)) => return Some((event, now)),
+                Some(Line::Command(command)) => self.command = Some(command),
+                None => {}
+            }
+        }
+        None
+    }
+}
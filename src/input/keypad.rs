@@ -0,0 +1,69 @@
+//! GPIO matrix keypad: rows driven low one at a time, columns read through pull-ups.
+//! The click detection and the keymap are the ones of `timesaver_core::input::keypad`.
+
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+
+pub use timesaver_core::input::keypad::KeyMatrix;
+
+use crate::button::Timings;
+use crate::millis::Instant;
+
+use super::{InputSource, UiEvent};
+
+/// Matrix keypad, with the UI event of each key (None for keys not used).
+/// Pins of different ports can be used through the HAL erased pins.
+pub struct Keypad<ROW, COL, const ROWS: usize, const COLS: usize> {
+    rows: [ROW; ROWS],
+    cols: [COL; COLS],
+    matrix: KeyMatrix<ROWS, COLS>,
+}
+
+impl<ROW, COL, const ROWS: usize, const COLS: usize> Keypad<ROW, COL, ROWS, COLS>
+where
+    ROW: OutputPin,
+    COL: InputPin,
+{
+    pub fn new(
+        mut rows: [ROW; ROWS],
+        cols: [COL; COLS],
+        keymap: [[Option<UiEvent>; COLS]; ROWS],
+        timings: Timings,
+    ) -> Self {
+        // Idle rows are high, so that no key is read as pressed
+        for row in rows.iter_mut() {
+            row.set_high().ok();
+        }
+
+        Keypad { rows, cols, matrix: KeyMatrix::new(keymap, timings) }
+    }
+
+    /// Read every key, queueing the UI events of the detected clicks.
+    fn scan(&mut self, now_ms: u32) {
+        for (r, row) in self.rows.iter_mut().enumerate() {
+            row.set_low().ok();
+            let cols = &self.cols;
+            let pressed = core::array::from_fn(|c| cols[c].is_low().unwrap_or(false));
+            row.set_high().ok();
+            self.matrix.update_row(r, pressed, now_ms);
+        }
+    }
+
+    /// Give back the owned pins.
+    pub fn release(self) -> ([ROW; ROWS], [COL; COLS]) {
+        (self.rows, self.cols)
+    }
+}
+
+impl<ROW, COL, const ROWS: usize, const COLS: usize> InputSource for Keypad<ROW, COL, ROWS, COLS>
+where
+    ROW: OutputPin,
+    COL: InputPin,
+{
+    fn poll(&mut self, now: Instant) -> Option<(UiEvent, Instant)> {
+        if !self.matrix.has_pending() {
+            self.scan(now.as_millis() as u32);
+        }
+        // Keys pressed together are detected by the same scan, and so at the same time
+        self.matrix.pop().map(|event| (event, now))
+    }
+}
@@ -4,18 +4,49 @@
 
 extern crate alloc;
 
-use alloc::format;
 use core::panic::PanicInfo;
+use core::time::Duration;
 use cortex_m_rt::entry;
-use rtt_target::{rprintln, rtt_init_print};
+use rtt_target::{rprintln, rtt_init, set_print_channel};
+#[cfg(feature = "qei-encoder")]
+use stm32f7xx_hal::gpio::Alternate;
 use stm32f7xx_hal::gpio::{Edge, ExtiPin};
-use stm32f7xx_hal::{interrupt, pac, prelude::*};
-
-use lcd1602::custom_characters::{HEART_FULL, MAN_DANCING, MAN_STANDING};
-use lcd1602::LCD1602;
-
+use stm32f7xx_hal::gpio::{Input, PullUp};
+use stm32f7xx_hal::gpio::Pin;
+use stm32f7xx_hal::interrupt;
+use stm32f7xx_hal::{pac, prelude::*};
+
+#[cfg(feature = "qei-encoder")]
+use encoder_interface::{qei::Qei, SharedQei};
+#[cfg(not(feature = "qei-encoder"))]
+use encoder_interface::{Encoder, SharedEncoder};
+use encoder_interface::decoder::EncoderConfig;
+use encoder_interface::range::Policy;
+use encoder_interface::EncoderValue;
+use button::{Button, SharedButton, Timings};
+use clock::DateTime;
+use config::DeviceConfig;
+use events::EventQueue;
+use input::commands::{Command, TextCommands};
+#[cfg(feature = "keypad")]
+use input::keypad::Keypad;
+#[cfg(feature = "keypad")]
+use input::UiEvent;
+use input::{InputSource, KnobInput};
+use timesaver::{Effect, Event, Now, TimeSaver, TimeSaverState};
+
+use lcd1602::{CharDisplay, ParallelBus, TextBuffer, LCD1602};
+use millis::calibration::ReferenceFit;
+use timesaver_core::{timesaver, ui};
+
+mod button;
+mod clock;
+mod config;
 mod encoder_interface;
+mod events;
+mod input;
 mod millis;
+mod sync;
 mod utilities;
 
 #[panic_handler]
@@ -26,22 +57,59 @@ fn panic(_info: &PanicInfo) -> ! {
 
 // static LED_3: Mutex<RefCell<Option<Pin<'B', 14, Output>>>> = Mutex::new(RefCell::new(None));
 
-#[derive(PartialEq, Debug, Clone, Copy)]
-enum TimeSaverState {
-    Splash,
-    Setting,
-    Count,
-    Alarm,
-}
+/// Seconds of the RTC the drift of the timers is measured over, when calibrating them against the LSE.
+const CALIBRATION_SECONDS: u32 = 20;
+
+/// Sampling rate of the encoder pins in Hz, or None to sample them on every edge through external interrupts.
+const ENCODER_POLLING_RATE: Option<u32> = None;
+
+/// Rotations and clicks of the knob, in the order they happened.
+static INPUT_EVENTS: EventQueue = EventQueue::new();
+
+/// Sampling rate of the pushbutton in Hz, used as TIM7 rate unless the encoder pins are polled too.
+const BUTTON_POLLING_RATE: u32 = 200;
+
+/// Period of the checks for text commands on the serial console, which raises no interrupt, in milliseconds, while
+/// a host may be sending some.
+const COMMAND_POLLING_MS: u64 = 100;
+
+/// Period of the scans of the keypad, which raises no interrupt, in milliseconds: well within the debounce time.
+#[cfg(feature = "keypad")]
+const KEYPAD_SCAN_MS: u64 = 5;
+
+/// Knob selecting the minutes to go, decoded in software: DT -> PB1 and CLK -> PB5.
+#[cfg(not(feature = "qei-encoder"))]
+static KNOB: SharedEncoder<Pin<'B', 1, Input<PullUp>>, Pin<'B', 5, Input<PullUp>>> =
+    SharedEncoder::with_events(&INPUT_EVENTS);
 
-const DEFAULT_MINUTES_TO_GO: u32 = 20;
+/// Knob selecting the minutes to go, decoded in hardware: DT -> PC6 (TIM3_CH1) and CLK -> PC7 (TIM3_CH2).
+#[cfg(feature = "qei-encoder")]
+static KNOB: SharedQei<pac::TIM3, (Pin<'C', 6, Alternate<2>>, Pin<'C', 7, Alternate<2>>)> =
+    SharedQei::with_events(&INPUT_EVENTS);
+
+/// Encoder pushbutton, on PB10.
+static KNOB_BUTTON: SharedButton<Pin<'B', 10, Input<PullUp>>> = SharedButton::new(&INPUT_EVENTS);
 
 #[entry]
 fn main() -> ! {
     utilities::init_mem_allocator();
 
-    // Initialize serial console
-    rtt_init_print!();
+    // Initialize serial console, also receiving text commands
+    let rtt_channels = rtt_init! {
+        up: {
+            0: {
+                size: 1024
+                name: "Terminal"
+            }
+        }
+        down: {
+            0: {
+                size: 32 // a whole clock setting command
+                name: "Terminal"
+            }
+        }
+    };
+    set_print_channel(rtt_channels.up.0);
 
     let core_perip = cortex_m::peripheral::Peripherals::take().unwrap();
     let dev_perip = pac::Peripherals::take().unwrap();
@@ -51,14 +119,11 @@ fn main() -> ! {
     let clocks = rcc.cfgr.sysclk(216.MHz()).freeze(); // lock configurations
     let d = core_perip.SYST.delay(&clocks);
 
-    // External interrupts stuff
-    let mut sys_cfg = dev_perip.SYSCFG;
-    let mut apb2 = rcc.apb2; // Advanced Peripheral Bus 2 (APB2) registers
-    let mut exti = dev_perip.EXTI; // External Interrupt Pin interface
-
     // Timer interrupt stuff
-    let tim2_counter = dev_perip.TIM2.counter_us(&clocks);
-    millis::init(tim2_counter);
+    millis::init(dev_perip.TIM2, &clocks);
+
+    // Wall clock, still running if it was set before the reset
+    clock::init();
 
     // I/O setup
     let mut led_1 = gpio_b.pb0.into_push_pull_output();
@@ -73,187 +138,294 @@ fn main() -> ! {
     let d5 = gpio_b.pb13.into_push_pull_output();
     let d6 = gpio_b.pb15.into_push_pull_output();
     let d7 = gpio_b.pb8.into_push_pull_output();
-    let mut lcd_backlight = gpio_b.pb9.into_push_pull_output();
+    let lcd_backlight = gpio_b.pb9.into_push_pull_output();
 
-    // Encoder pins
-    let mut encoder_dt = gpio_b.pb1.into_pull_up_input();
-    let mut encoder_clk = gpio_b.pb5.into_pull_up_input();
-    let encoder_pushbutton = gpio_b.pb10.into_pull_up_input();
+    // Settings depending on the unit, e.g. the encoder model
+    let mut device_config = config::load();
+    let mut flash = dev_perip.FLASH;
+    rprintln!("{:?}", device_config);
+    millis::set_correction(device_config.clock_ppm);
 
-    // Enable external interrupts on rotary encoder pins (ALTERNATIVE: pool them with a timer at ~900Hz)
+    // External interrupts stuff
     // - External interrupts: https://stackoverflow.com/questions/56179131/cannot-receive-interrupt-on-pe0-stm32
     // - EXTI register logics: https://stm32f4-discovery.net/2014/08/stm32f4-external-interrupts-tutorial/
-    encoder_dt.make_interrupt_source(&mut sys_cfg, &mut apb2);
-    encoder_dt.trigger_on_edge(&mut exti, Edge::RisingFalling);
-    encoder_dt.enable_interrupt(&mut exti);
-    unsafe { pac::NVIC::unmask(interrupt::EXTI1) } // enable Line1 interrupt (because the pin is PB1)
-    encoder_clk.make_interrupt_source(&mut sys_cfg, &mut apb2);
-    encoder_clk.trigger_on_edge(&mut exti, Edge::RisingFalling);
-    encoder_clk.enable_interrupt(&mut exti);
-    unsafe { pac::NVIC::unmask(interrupt::EXTI9_5) } // enable Line5 interrupt (because the pin is PB5)
+    let mut sys_cfg = dev_perip.SYSCFG;
+    let mut apb2 = rcc.apb2; // Advanced Peripheral Bus 2 (APB2) registers
+    let mut exti = dev_perip.EXTI; // External Interrupt Pin interface
+
+    // Encoder pushbutton, whose press starts sampling it
+    let mut encoder_pushbutton = gpio_b.pb10.into_pull_up_input();
+    encoder_pushbutton.make_interrupt_source(&mut sys_cfg, &mut apb2);
+    encoder_pushbutton.trigger_on_edge(&mut exti, Edge::Falling);
+    encoder_pushbutton.enable_interrupt(&mut exti);
+    unsafe { pac::NVIC::unmask(interrupt::EXTI15_10) } // enable Line10 interrupt (because the pin is PB10)
+
+    // Double clicks are not used: don't delay short presses waiting for a second one
+    KNOB_BUTTON.init(Button::new(encoder_pushbutton, Timings { double_click_ms: 0, ..Timings::default() }));
+
+    // Sample the pushbutton (and the encoder pins, if polled) from TIM7 interrupts. Unless the encoder needs them,
+    // they stop while the pushbutton is idle, so as not to wake the core up for nothing.
+    let polling_rate_hz = ENCODER_POLLING_RATE.unwrap_or(BUTTON_POLLING_RATE);
+    let encoder_polled = ENCODER_POLLING_RATE.is_some() || cfg!(feature = "qei-encoder");
+    encoder_interface::start_polling(dev_perip.TIM7.counter_hz(&clocks), polling_rate_hz, encoder_polled);
+
+    // Hardware decoding: DT -> PC6 (TIM3_CH1) and CLK -> PC7 (TIM3_CH2)
+    #[cfg(feature = "qei-encoder")]
+    {
+        let gpio_c = dev_perip.GPIOC.split();
+        let encoder_dt = gpio_c.pc6.into_alternate::<2>().internal_pull_up(true);
+        let encoder_clk = gpio_c.pc7.into_alternate::<2>().internal_pull_up(true);
+        let qei = Qei::new(dev_perip.TIM3, (encoder_dt, encoder_clk), 0x0f); // strongest input filter
+        KNOB.init(qei.with_config(device_config.encoder));
+    }
 
-    encoder_interface::init_encoder(encoder_dt, encoder_clk);
+    // Software decoding: DT -> PB1 and CLK -> PB5
+    #[cfg(not(feature = "qei-encoder"))]
+    {
+        let mut encoder_dt = gpio_b.pb1.into_pull_up_input();
+        let mut encoder_clk = gpio_b.pb5.into_pull_up_input();
+
+        // Without polling, sample the pins on their edges
+        if ENCODER_POLLING_RATE.is_none() {
+            // Enable external interrupts on rotary encoder pins
+            encoder_dt.make_interrupt_source(&mut sys_cfg, &mut apb2);
+            encoder_dt.trigger_on_edge(&mut exti, Edge::RisingFalling);
+            encoder_dt.enable_interrupt(&mut exti);
+            unsafe { pac::NVIC::unmask(interrupt::EXTI1) } // enable Line1 interrupt (because the pin is PB1)
+            encoder_clk.make_interrupt_source(&mut sys_cfg, &mut apb2);
+            encoder_clk.trigger_on_edge(&mut exti, Edge::RisingFalling);
+            encoder_clk.enable_interrupt(&mut exti);
+            unsafe { pac::NVIC::unmask(interrupt::EXTI9_5) } // enable Line5 interrupt (because the pin is PB5)
+        }
+
+        KNOB.init(Encoder::new(encoder_dt, encoder_clk).with_config(device_config.encoder));
+        KNOB.set_acceleration(encoder_interface::acceleration::DEFAULT_CURVE);
+    }
+    seed_knob_position(&device_config.encoder);
+
+    // Keypad besides the knob: rows PE2 and PE4, columns PE5 and PE6, with Up and Down on the first row, and
+    // Select and Back on the second
+    #[cfg(feature = "keypad")]
+    let mut keypad = {
+        let gpio_e = dev_perip.GPIOE.split();
+        let rows = [gpio_e.pe2.into_push_pull_output().erase(), gpio_e.pe4.into_push_pull_output().erase()];
+        let cols = [gpio_e.pe5.into_pull_up_input().erase(), gpio_e.pe6.into_pull_up_input().erase()];
+        let keymap = [[Some(UiEvent::Up(1)), Some(UiEvent::Down(1))], [Some(UiEvent::Select), Some(UiEvent::Back)]];
+        Keypad::new(rows, cols, keymap, Timings { double_click_ms: 0, ..Timings::default() })
+    };
 
     // LCD setup
-    let mut lcd = LCD1602::new(en, rs, d4, d5, d6, d7, d).unwrap();
+    let lcd_bus = ParallelBus::new(en, rs, d4, d5, d6, d7).with_backlight(lcd_backlight);
+    let lcd_init_start_us = millis::micros().unwrap();
+    let mut lcd = LCD1602::with_bus(lcd_bus, d).unwrap();
     // lcd.set_display(true, true, false).unwrap();
     lcd.init_custom_chars().unwrap();
+    rprintln!("LCD initialised in {} us", millis::micros().unwrap() - lcd_init_start_us);
+
+    // From now on, the UI only relies on the CharDisplay interface
+    let display = &mut lcd;
+
+    // Screens are rendered in memory, and only the changes are copied to the display
+    let mut screen: TextBuffer<2, 16> = TextBuffer::new();
+    let mut displayed: TextBuffer<2, 16> = TextBuffer::new(); // blank, as just initialised
 
-    let mut current_state = TimeSaverState::Splash;
-    let mut previous_state = TimeSaverState::Alarm; // this differs from current_state, in order to perform the first one-time action
-    let mut minutes_to_go = 0u32;
+    let mut timesaver = TimeSaver::new();
+    let mut dropped_events = 0u32;
+    #[cfg(feature = "cs-count")]
+    let mut critical_sections = sync::critical_sections();
+    let mut reference_fit = ReferenceFit::new();
+
+    // UI events come from the knob (and the keypad, if any), or from the serial console (e.g. to drive the
+    // TimeSaver remotely)
+    let mut knob_input = KnobInput::new(&INPUT_EVENTS);
+    let mut serial_commands = TextCommands::new(rtt_channels.down.0);
 
     rprintln!("Everything is set up!");
     led_1.toggle();
-    lcd_backlight.set_high();
+    display.set_backlight(displayed.backlight()).unwrap();
 
+    let mut effects = timesaver.start(Now { instant: millis::now_instant().unwrap(), wall_clock: clock::now() });
     loop {
-        let now_ms = millis::now().unwrap();
+        // Perform the effects of the last event on the hardware
+        let previous_state = timesaver.state();
+        for effect in effects {
+            match effect {
+                Effect::Redraw => {
+                    ui::render(&mut screen, &timesaver, clock::now()).unwrap();
+                    ui::copy(display, &screen, &mut displayed).unwrap();
+                }
+                Effect::Led(on) => {
+                    if on {
+                        led_2.set_high();
+                    } else {
+                        led_2.set_low();
+                    }
+                }
+                Effect::SetClock(date_time) => set_clock(&date_time),
+                Effect::SetAlarm(time) => clock::set_alarm(&time).unwrap(),
+                Effect::CancelAlarm => clock::cancel_alarm(),
+                Effect::Fault(error) => rprintln!("Timed action not scheduled: {:?}", error),
+            }
+        }
 
-        // Get button state at 5Hz (due to debouncing)
-        let button_short_click = if now_ms % 200 == 0 {
-            encoder_pushbutton.is_low()
+        let now = Now { instant: millis::now_instant().unwrap(), wall_clock: clock::now() };
+
+        // Take a single event per iteration: the alarm first, or a UI event from whichever source has one
+        let alarm_rang = clock::take_alarm();
+        let ui_event = if alarm_rang {
+            None
         } else {
-            false
+            let ui_event = knob_input.poll(now.instant);
+            #[cfg(feature = "keypad")]
+            let ui_event = ui_event.or_else(|| keypad.poll(now.instant));
+            ui_event.or_else(|| serial_commands.poll(now.instant))
+        };
+        let command = serial_commands.take_command();
+        if INPUT_EVENTS.dropped() != dropped_events {
+            dropped_events = INPUT_EVENTS.dropped();
+            rprintln!("Input events dropped so far: {}", dropped_events);
+        }
+        #[cfg(feature = "cs-count")]
+        if let Some((ui_event, _)) = ui_event {
+            let entered = sync::critical_sections();
+            rprintln!("{:?} after {} critical sections", ui_event, entered.wrapping_sub(critical_sections));
+            critical_sections = entered;
+        }
+        let event = match ui_event {
+            Some((ui_event, at)) => Event::Ui(ui_event, at),
+            None if alarm_rang => Event::AlarmRang,
+            None => Event::Tick,
         };
-        // let button_long_click = button_short_click; // TODO: implement long clicks
 
-        // Trigger state changes
-        if button_short_click {
-            // Switch on LCD backlight if exiting from Alarm state
-            if current_state == TimeSaverState::Alarm {
-                lcd_backlight.set_high();
+        // Device maintenance is up to the firmware, not to the TimeSaver
+        let mut redraw = false;
+        match command {
+            // The clock can be set remotely at any time
+            Some(Command::SetClock(date_time)) => {
+                set_clock(&date_time);
+                redraw = true;
             }
+            Some(Command::Rename(index, label)) => redraw = timesaver.rename(index, label),
 
-            // Update state
-            current_state = match current_state {
-                TimeSaverState::Splash => TimeSaverState::Setting,
-                TimeSaverState::Setting => TimeSaverState::Count,
-                TimeSaverState::Alarm => TimeSaverState::Splash,
-                _ => current_state, // keep same state otherwise (just the case of TimeSaverState::Count)
+            // The drift of the timers can be measured against a reference at any time...
+            Some(Command::Reference(reference_ms)) => {
+                reference_fit.add(reference_ms.saturating_mul(1000), millis::counter_micros().unwrap());
             }
-            // } else if button_long_click {
-            //     current_state = match current_state {
-            //         TimeSaverState::Count => TimeSaverState::Setting,
-            //         _ => current_state, // keep same state otherwise
-            //     }
-        }
-        let state_changed = current_state != previous_state;
-
-        // Perform one-time actions needed when the state has changed
-        // NOTE: current_state is the new state just achieved
-        if state_changed {
-            rprintln!("-> State moved to {:?}", current_state); // enum name can be printed thanks to the Debug trait
-            match current_state {
-                TimeSaverState::Splash => {
-                    lcd.clear().unwrap();
-                    lcd.print("Save your time ").unwrap();
-                    lcd.write_custom_char(HEART_FULL).unwrap();
+            Some(Command::CalibrateReference) => match reference_fit.ppm() {
+                Some(ppm) => {
+                    apply_drift(&mut flash, &mut device_config, ppm);
+                    reference_fit = ReferenceFit::new();
                 }
-
-                TimeSaverState::Setting => {
-                    encoder_interface::set(DEFAULT_MINUTES_TO_GO as i32 - 1);
-                    minutes_to_go = DEFAULT_MINUTES_TO_GO;
-
-                    lcd.clear().unwrap();
-                    lcd.print("Set time:").unwrap();
-                    lcd.set_cursor(1, 9).unwrap();
-                    lcd.print("min").unwrap();
-                }
-
-                TimeSaverState::Count => {
-                    lcd.clear().unwrap();
-                    lcd.print("Try to focus...").unwrap();
-                    lcd.set_cursor(1, 0).unwrap();
-                    lcd.print(&format!(
-                        "{: >3} min left", // right-aligned with 3 digits (including sign)
-                        minutes_to_go - 1
-                    ))
-                    .unwrap();
+                None => {
+                    let span_s = reference_fit.span_us() / 1_000_000;
+                    rprintln!("Reference timestamps over {} s, not enough to calibrate", span_s);
                 }
-
-                TimeSaverState::Alarm => {
-                    lcd.clear().unwrap();
-                    lcd.set_cursor(0, 2).unwrap();
-                    lcd.print("TIME IS UP!!").unwrap();
+            },
+
+            // ...while measuring it against the LSE blocks for a while, so only when idle, with nothing counting down
+            Some(Command::Calibrate) if timesaver.state() == TimeSaverState::Splash && !timesaver.is_counting() => {
+                rprintln!("Calibrating against the LSE for {} s...", CALIBRATION_SECONDS);
+                let knob_position = KNOB.get();
+                match millis::measure_drift(CALIBRATION_SECONDS) {
+                    Ok(ppm) => apply_drift(&mut flash, &mut device_config, ppm),
+                    Err(error) => rprintln!("Calibration failed: {:?}", error),
                 }
+                // Turns and clicks meanwhile were meant for a TimeSaver that could not react
+                INPUT_EVENTS.clear();
+                KNOB.set(knob_position);
             }
-            previous_state = current_state; // copied thanks to "Clone, Copy" traits
-        }
-
-        // Perform timed actions for the current state
-        match current_state {
-            TimeSaverState::Setting => {
-                // Update encoder selection at 20Hz
-                if now_ms % 50 == 0 {
-                    // Get current encoder value, if it's positive, otherwise reset it
-                    minutes_to_go = u32::try_from(encoder_interface::get()).unwrap_or_else(|_| {
-                        encoder_interface::reset();
-                        0
-                    }) + 1; // avoid that the timer is set to 0
-
-                    lcd.set_cursor(1, 5).unwrap();
-                    lcd.print(&format!(
-                        "{: >3}", // right-aligned with 3 digits (including sign)
-                        minutes_to_go
-                    ))
-                    .unwrap();
+            Some(Command::Calibrate) => rprintln!("Calibration only runs on the splash screen, with nothing counting"),
+
+            // The knob fitted, or how it is mounted, changed
+            Some(Command::Encoder(setting)) => {
+                device_config.encoder = device_config.encoder.with(setting);
+                KNOB.set_config(device_config.encoder);
+                seed_knob_position(&device_config.encoder);
+                match config::store(&mut flash, &device_config) {
+                    Ok(()) => rprintln!("{:?} applied and stored", device_config.encoder),
+                    Err(error) => rprintln!("{:?} applied, but not stored: {:?}", device_config.encoder, error),
                 }
             }
+            None => {}
+        }
 
-            TimeSaverState::Count => {
-                // Update remaining time each minute
-                if now_ms % 60_000 == 0 {
-                    if let Some(sub_result) = minutes_to_go.checked_sub(1) {
-                        minutes_to_go = sub_result;
-                    } else {
-                        // Move to alarm state
-                        current_state = TimeSaverState::Alarm;
-                        continue;
-                    }
+        effects = timesaver.handle(event, now);
+        if redraw && !effects.contains(&Effect::Redraw) {
+            effects.push(Effect::Redraw).unwrap_or(()); // a single effect of each kind
+        }
+        if timesaver.state() != previous_state {
+            // enum name printed thanks to the Debug trait
+            rprintln!("-> State moved to {:?}, knob at {}", timesaver.state(), KNOB.get());
+        }
 
-                    // Update timer printed value
-                    lcd.set_cursor(1, 0).unwrap();
-                    lcd.print(&format!(
-                        "{: >3} min", // right-aligned with 3 digits (including sign)
-                        minutes_to_go
-                    ))
-                    .unwrap();
+        // Go to deep-sleep until the next interrupt (input or timed action), unless more events may be waiting.
+        // Interrupts are disabled meanwhile, so that none can slip in between the checks and the sleep: a pending
+        // one still wakes the core up, and is handled as soon as they are enabled again.
+        // Commands on the serial console raise no interrupt, so wake up to check them at least every so often while
+        // a host is attached or one is partly received, and so does the keypad, which is scanned. Otherwise, only
+        // the deadlines of the TimeSaver wake the core up.
+        if event == Event::Tick && command.is_none() && effects.is_empty() {
+            let polling_ms = (debugger_attached() || serial_commands.is_receiving()).then_some(COMMAND_POLLING_MS);
+            #[cfg(feature = "keypad")]
+            let polling_ms = Some(polling_ms.map_or(KEYPAD_SCAN_MS, |ms| ms.min(KEYPAD_SCAN_MS)));
+            let polling = polling_ms.map(|ms| now.instant.saturating_add(Duration::from_millis(ms)));
+            let wakeup = match (timesaver.next_deadline(), polling) {
+                (Some(deadline), Some(polling)) => Some(deadline.min(polling)),
+                (deadline, polling) => deadline.or(polling),
+            };
+            sync::free(|_| {
+                millis::set_wakeup(wakeup);
+                if INPUT_EVENTS.is_empty() && !clock::alarm_pending() {
+                    cortex_m::asm::wfi();
                 }
+            });
+        }
+    }
+}
 
-                // Character animation (bottom-right of the screen) at 2Hz
-                if now_ms % 500 == 0 {
-                    led_2.toggle();
-                    lcd.set_cursor(1, 15).unwrap();
-                    if led_2.is_set_high() {
-                        lcd.write_custom_char(MAN_STANDING).unwrap();
-                    } else {
-                        lcd.write_custom_char(MAN_DANCING).unwrap();
-                    }
-                }
-            }
+/// Whether a debug probe is attached (C_DEBUGEN set in DHCSR), e.g. an RTT host which may send commands.
+/// It stays set until the next reset, even once the probe is gone.
+fn debugger_attached() -> bool {
+    // DCB::is_debugger_attached() only comes with cortex-m 0.7
+    unsafe { (*cortex_m::peripheral::DCB::ptr()).dhcsr.read() & 1 != 0 }
+}
 
-            TimeSaverState::Alarm => {
-                // Blink LCD backlight at 2Hz
-                if now_ms % 500 == 0 {
-                    lcd_backlight.toggle();
-                }
-            }
+/// Set the wall clock, reporting the outcome on the console.
+fn set_clock(date_time: &DateTime) {
+    match clock::set(date_time) {
+        Ok(()) => rprintln!("Clock set to {:?}", date_time),
+        Err(error) => rprintln!("Clock not set: {:?}", error),
+    }
+}
 
-            _ => {}
-        }
+/// Count the position of the knob within a revolution of the given encoder, from where it is now.
+/// It follows the steps delivered to the TimeSaver, e.g. to tell on the console where the knob was left.
+fn seed_knob_position(config: &EncoderConfig) {
+    let steps = config.steps_per_revolution() as i32;
+    KNOB.set_policy(Policy::wrap(0, steps - 1, 1));
+    KNOB.reset();
+}
 
-        // Go to deep-sleep until the next interrupt
-        cortex_m::asm::wfi();
+/// Correct the time for the given drift of the timers from now on, and store it in the device configuration.
+fn apply_drift(flash: &mut pac::FLASH, device_config: &mut DeviceConfig, ppm: i32) {
+    millis::set_correction(ppm);
+    device_config.clock_ppm = ppm;
+    match config::store(flash, device_config) {
+        Ok(()) => rprintln!("Clock drift of {} ppm corrected and stored", ppm),
+        Err(error) => rprintln!("Clock drift of {} ppm corrected, but not stored: {:?}", ppm, error),
     }
 }
 
-#[interrupt]
-fn EXTI1() {
-    encoder_interface::handle_encoder_interrupt(encoder_interface::InterruptedPin::DtPin);
+#[cfg(not(feature = "qei-encoder"))]
+encoder_interface::encoder_interrupts! {
+    exti EXTI1 => [KNOB]; // DT on PB1
+    exti EXTI9_5 => [KNOB]; // CLK on PB5
+    exti EXTI15_10 => [KNOB_BUTTON]; // pushbutton on PB10
+    polling TIM7 => [KNOB, KNOB_BUTTON]; // the knob is also sampled on its edges, unless ENCODER_POLLING_RATE is set
 }
 
-#[interrupt]
-fn EXTI9_5() {
-    encoder_interface::handle_encoder_interrupt(encoder_interface::InterruptedPin::ClkPin);
+#[cfg(feature = "qei-encoder")]
+encoder_interface::encoder_interrupts! {
+    exti EXTI15_10 => [KNOB_BUTTON]; // pushbutton on PB10
+    polling TIM7 => [KNOB, KNOB_BUTTON]; // the knob is sampled to report its rotations as events
 }
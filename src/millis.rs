@@ -1,67 +1,217 @@
 //! Millis
-//! Count milliseconds from start time, through interrupts on TIM2.
+//! Count milliseconds from start time, through TIM2.
+//! The counter is 64-bit, so it never wraps: `now_instant` gives it whole, while `now` truncates it to 32 bits
+//! (wrapping every ~49.7 days), which is enough for wrap-aware intervals (`wrapping_sub`).
+//!
+//! TIM2 is free-running at 1 MHz over its whole 32-bit range, extended in software by counting its overflows,
+//! so that time is always exact to the microsecond. Besides the overflows (every ~71.6 minutes), it only raises
+//! an interrupt at the wakeup armed with `set_wakeup`, leaving the core asleep until something needs doing.
+//!
+//! TIM2 runs from the system clock, which drifts (especially from the internal oscillator): its microseconds are
+//! corrected by the drift set with `set_correction`, measured against the LSE or against a reference.
 
-use core::cell::{Cell, RefCell};
-use core::ops::DerefMut;
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
 
-use cortex_m::interrupt::{free, Mutex};
 use stm32f7xx_hal::pac::TIM2;
-use stm32f7xx_hal::timer::{CounterUs, Event};
-use stm32f7xx_hal::{interrupt, pac, prelude::*};
+use stm32f7xx_hal::rcc::Clocks;
+use stm32f7xx_hal::{interrupt, pac};
 
-/// Accuracy of the counter, in milliseconds.
-const ACCURACY_MS: u32 = 5;
+use crate::{clock, sync};
 
-static MILLIS: Mutex<RefCell<Option<MillisStruct>>> = Mutex::new(RefCell::new(None));
+pub use timesaver_core::millis::{calibration, Instant};
 
-struct MillisStruct {
-    current_ms: Cell<u32>,
-    tim2: RefCell<CounterUs<TIM2>>,
-}
+use timesaver_core::millis::overflow::{self, OverflowCounter};
+
+/// Frequency TIM2 counts at.
+const COUNTER_HZ: u32 = 1_000_000;
+
+/// Overflows of TIM2 counter, i.e. high word of the microseconds: only written by the TIM2 interrupt.
+static OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+
+static INITIALISED: AtomicBool = AtomicBool::new(false);
+
+/// Drift correction, applied from the counter microseconds it was set at (as low and high words), which were
+/// then the corrected microseconds given: time never jumps when it changes. These are only written in critical
+/// sections, so that interrupts never see them half-updated.
+static CORRECTION_PPM: AtomicI32 = AtomicI32::new(0);
+static CORRECTION_COUNTER_US: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+static CORRECTION_US: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+/// Status register flags (cleared writing 0).
+const SR_UIF: u32 = 1 << 0;
+const SR_CC1IF: u32 = 1 << 1;
 
 #[derive(Debug)]
 pub enum Error {
     TimerNotInitialised,
+    /// The measured drift is beyond `calibration::MAX_PPM`, i.e. something went wrong measuring it.
+    DriftOutOfRange,
 }
 
-/// Initialise millis counting, using TIM2.
-pub fn init(mut tim2_counter: CounterUs<TIM2>) {
-    // Set timer counts
-    tim2_counter.start(ACCURACY_MS.millis()).unwrap();
-    tim2_counter.listen(Event::Update);
+fn load_u64(words: &[AtomicU32; 2]) -> u64 {
+    ((words[1].load(Ordering::Acquire) as u64) << 32) | words[0].load(Ordering::Acquire) as u64
+}
 
-    // Initialise static struct
-    free(|cs| {
-        let millis = MILLIS.borrow(cs);
-        millis.replace(Some(MillisStruct {
-            current_ms: Cell::new(0u32),
-            tim2: RefCell::new(tim2_counter),
-        }));
-    });
+fn store_u64(words: &[AtomicU32; 2], value: u64) {
+    words[0].store(value as u32, Ordering::Release);
+    words[1].store((value >> 32) as u32, Ordering::Release);
+}
+
+/// Initialise time counting, running TIM2 freely.
+pub fn init(tim2: TIM2, clocks: &Clocks) {
+    // Enable and reset timer
+    let rcc = unsafe { &(*pac::RCC::ptr()) };
+    rcc.apb1enr.modify(|_, w| w.tim2en().set_bit());
+    rcc.apb1rstr.modify(|_, w| w.tim2rst().set_bit());
+    rcc.apb1rstr.modify(|_, w| w.tim2rst().clear_bit());
+
+    // Count microseconds over the whole 32-bit range
+    let prescaler = clocks.timclk1().raw() / COUNTER_HZ - 1;
+    tim2.psc.write(|w| w.psc().bits(prescaler as u16));
+    tim2.arr.write(|w| unsafe { w.bits(u32::MAX) });
+    tim2.egr.write(|w| w.ug().set_bit()); // load the prescaler
+    tim2.sr.write(|w| unsafe { w.bits(0) });
+
+    // Interrupts on overflows, and on compare matches of channel 1 for the wakeups. The compare interrupt is
+    // left enabled, as an unarmed channel just matches once per overflow.
+    tim2.dier.write(|w| w.uie().set_bit().cc1ie().set_bit());
+    tim2.ccr1.write(|w| unsafe { w.bits(u32::MAX) });
+    tim2.cr1.modify(|_, w| w.cen().set_bit());
+    INITIALISED.store(true, Ordering::Release);
 
     // Enable timer interrupts
     unsafe { pac::NVIC::unmask(interrupt::TIM2) };
 }
 
-/// Get current milliseconds.
+/// Get current instant, without entering a critical section.
+pub fn now_instant() -> Result<Instant, Error> {
+    micros().map(|us| Instant::from_millis(us / 1000))
+}
+
+/// Get current milliseconds, truncated to 32 bits.
 pub fn now() -> Result<u32, Error> {
-    free(|cs| match MILLIS.borrow(cs).borrow_mut().deref_mut() {
-        Some(millis) => Ok(millis.current_ms.get()),
-        None => Err(Error::TimerNotInitialised),
-    })
+    now_instant().map(|instant| instant.as_millis() as u32)
+}
+
+/// Get current microseconds, corrected for the drift, without entering a critical section.
+/// It must not be called from interrupts preempting TIM2 one, which would never see the overflows counted.
+pub fn micros() -> Result<u64, Error> {
+    counter_micros().map(from_counter_micros)
+}
+
+/// Corrected microseconds corresponding to the given counter ones, at least the current correction base.
+fn from_counter_micros(counter_us: u64) -> u64 {
+    let elapsed_us = counter_us.saturating_sub(load_u64(&CORRECTION_COUNTER_US));
+    load_u64(&CORRECTION_US) + calibration::correct(elapsed_us, CORRECTION_PPM.load(Ordering::Acquire))
+}
+
+/// Counter microseconds corresponding to the given corrected ones, at least the current correction base.
+fn to_counter_micros(us: u64) -> u64 {
+    let elapsed_us = us.saturating_sub(load_u64(&CORRECTION_US));
+    load_u64(&CORRECTION_COUNTER_US) + calibration::uncorrect(elapsed_us, CORRECTION_PPM.load(Ordering::Acquire))
+}
+
+/// Get the microseconds counted by TIM2, without drift correction (e.g. to measure the drift itself), with the
+/// same constraints as `micros`.
+pub fn counter_micros() -> Result<u64, Error> {
+    if !INITIALISED.load(Ordering::Acquire) {
+        return Err(Error::TimerNotInitialised);
+    }
+
+    Ok(overflow::read(&mut Tim2Counter))
+}
+
+/// TIM2 counter, with the overflows counted by its interrupt.
+struct Tim2Counter;
+
+impl OverflowCounter for Tim2Counter {
+    fn overflows(&mut self) -> u32 {
+        OVERFLOWS.load(Ordering::Acquire)
+    }
+
+    fn counter(&mut self) -> u32 {
+        unsafe { (*TIM2::ptr()).cnt.read().bits() }
+    }
+
+    fn overflow_pending(&mut self) -> bool {
+        unsafe { (*TIM2::ptr()).sr.read().uif().bit_is_set() }
+    }
+}
+
+/// Correct the time from now on for the given drift of TIM2, in ppm (see `calibration`).
+pub fn set_correction(ppm: i32) {
+    sync::free(|_| {
+        // Rebase on the current time, which stays the same for the new correction
+        if let Ok(counter_us) = counter_micros() {
+            store_u64(&CORRECTION_US, from_counter_micros(counter_us));
+            store_u64(&CORRECTION_COUNTER_US, counter_us);
+        }
+        CORRECTION_PPM.store(ppm.clamp(-calibration::MAX_PPM, calibration::MAX_PPM), Ordering::Release);
+    });
+}
+
+/// Drift of TIM2 currently corrected, in ppm.
+pub fn correction() -> i32 {
+    CORRECTION_PPM.load(Ordering::Acquire)
+}
+
+/// Measure the drift of TIM2 against the LSE, over the given seconds of the RTC (which must be running).
+/// It blocks for that long: the RTC seconds only tick every 2 LSE cycles (61 us), so the longer, the better.
+pub fn measure_drift(seconds: u32) -> Result<i32, Error> {
+    clock::wait_for_next_second();
+    let start_us = counter_micros()?;
+    for _ in 0..seconds {
+        clock::wait_for_next_second();
+    }
+    let measured_us = counter_micros()? - start_us;
+    calibration::ppm(seconds as u64 * COUNTER_HZ as u64, measured_us).ok_or(Error::DriftOutOfRange)
+}
+
+/// Arm TIM2 to wake the core up at the given deadline (e.g. the next one of a scheduler), or just on the next
+/// overflow if None. Deadlines already passed wake it up straight away, so that none is ever missed.
+pub fn set_wakeup(deadline: Option<Instant>) {
+    let now_us = match counter_micros() {
+        Ok(now_us) => now_us,
+        Err(_) => return,
+    };
+    let tim2 = unsafe { &*TIM2::ptr() };
+
+    // The compare channel matches the counter, which drifts
+    let deadline = deadline.map(|deadline| to_counter_micros(deadline.as_millis().saturating_mul(1000)));
+    match deadline {
+        Some(deadline_us) if deadline_us <= now_us => {
+            tim2.egr.write(|w| w.cc1g().set_bit()); // wake up now
+        }
+
+        // Within the next 2^32 us, the low word is enough for the compare channel
+        Some(deadline_us) if deadline_us - now_us <= u32::MAX as u64 => {
+            tim2.ccr1.write(|w| unsafe { w.bits(deadline_us as u32) });
+
+            // The deadline may have passed while arming the channel
+            if counter_micros().map_or(false, |now_us| now_us >= deadline_us) {
+                tim2.egr.write(|w| w.cc1g().set_bit());
+            }
+        }
+
+        // Too far, or no deadline at all: match as late as possible, after the overflow has woken the core up
+        _ => tim2.ccr1.write(|w| unsafe { w.bits((now_us as u32).wrapping_sub(1)) }),
+    }
 }
 
 #[interrupt]
 fn TIM2() {
-    free(|cs| {
-        if let Some(millis) = MILLIS.borrow(cs).borrow_mut().deref_mut() {
-            // Increment millis counter
-            millis
-                .current_ms
-                .replace(millis.current_ms.get() + ACCURACY_MS);
-
-            // Clear pending interrupt
-            millis.tim2.borrow_mut().clear_interrupt(Event::Update);
-        }
-    })
+    let tim2 = unsafe { &*TIM2::ptr() };
+    let sr = tim2.sr.read().bits();
+
+    // Flags are cleared straight on the status register, writing 1 to the others to leave them untouched
+    if sr & SR_UIF != 0 {
+        // Count overflow (this is the only writer)
+        OVERFLOWS.store(OVERFLOWS.load(Ordering::Relaxed).wrapping_add(1), Ordering::Release);
+        tim2.sr.write(|w| unsafe { w.bits(!SR_UIF) });
+    }
+
+    if sr & SR_CC1IF != 0 {
+        // Nothing else to do: waking up the core was the point
+        tim2.sr.write(|w| unsafe { w.bits(!SR_CC1IF) });
+    }
 }
@@ -0,0 +1,42 @@
+//! Sync
+//! Synchronisation primitives shared by interrupt handlers and the main loop.
+//!
+//! Values read often (encoder values, millis) live in atomics, so that reading them does not disable
+//! interrupts; critical sections are left to initialisation and to updates of non-atomic data. Building with
+//! `--features cs-count` counts them, to measure how often a code path disables interrupts.
+
+#[cfg(feature = "cs-count")]
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use cortex_m::interrupt::CriticalSection;
+use timesaver_core::events::Lock;
+
+/// Critical sections entered so far, through `free`.
+#[cfg(feature = "cs-count")]
+static CRITICAL_SECTIONS: AtomicU32 = AtomicU32::new(0);
+
+/// Execute the closure in a critical section, like `cortex_m::interrupt::free`, which all the modules go through.
+pub fn free<F, R>(f: F) -> R
+where
+    F: FnOnce(&CriticalSection) -> R,
+{
+    #[cfg(feature = "cs-count")]
+    CRITICAL_SECTIONS.fetch_add(1, Ordering::Relaxed);
+    cortex_m::interrupt::free(f)
+}
+
+/// Number of critical sections entered so far.
+#[cfg(feature = "cs-count")]
+pub fn critical_sections() -> u32 {
+    CRITICAL_SECTIONS.load(Ordering::Relaxed)
+}
+
+/// Critical sections of `free`, for the structures of `timesaver_core` shared with interrupts.
+pub struct Interrupts;
+
+// Interrupts are disabled while the closure runs, on a single core
+unsafe impl Lock for Interrupts {
+    fn free<R>(f: impl FnOnce() -> R) -> R {
+        free(|_| f())
+    }
+}
@@ -0,0 +1,350 @@
+//! Button
+//! Click detection of a debounced pushbutton: short, long and double clicks.
+//!
+//! The detection is a state machine fed with timestamped samples of the button level, free of any hardware
+//! dependency.
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum ButtonEvent {
+    /// Pressed, before knowing what kind of click it makes.
+    Pressed,
+    /// Pressed and released quickly, with no second press following it.
+    ShortPress,
+    /// Kept pressed for `Timings::long_press_ms`.
+    LongPress,
+    /// Pressed a second time within `Timings::double_click_ms` from a short press.
+    DoubleClick,
+    /// Still pressed after a long press, repeated every `Timings::repeat_ms`.
+    Held,
+    Released,
+}
+
+/// Timings of the click detection, in milliseconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Timings {
+    /// Time a level must be stable to be trusted.
+    pub debounce_ms: u32,
+    pub long_press_ms: u32,
+    /// Time allowed between a release and a second press to make a double click, 0 to disable double clicks.
+    /// Short presses are only reported once it expires.
+    pub double_click_ms: u32,
+    /// Period of `Held` events after a long press, 0 to disable key-repeat.
+    pub repeat_ms: u32,
+}
+
+impl Default for Timings {
+    fn default() -> Self {
+        Timings { debounce_ms: 20, long_press_ms: 800, double_click_ms: 250, repeat_ms: 200 }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Phase {
+    Idle,
+    /// First press, since the given time. `next_event_ms` is when the next LongPress/Held is due.
+    Pressed { since_ms: u32, next_event_ms: u32, long: bool },
+    /// Released after a short press, waiting for a second one.
+    Released { since_ms: u32 },
+    /// Second press of a double click, waiting for the release.
+    SecondPress,
+}
+
+/// Click detection status between two samples.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ButtonState {
+    /// Last raw level and when it changed.
+    raw: bool,
+    raw_since_ms: u32,
+    /// Debounced level.
+    pressed: bool,
+    phase: Phase,
+}
+
+impl ButtonState {
+    pub const fn new() -> Self {
+        ButtonState { raw: false, raw_since_ms: 0, pressed: false, phase: Phase::Idle }
+    }
+
+    /// Whether the button is released and no click is being detected, so that sampling it can stop until it is
+    /// pressed again.
+    pub fn is_idle(&self) -> bool {
+        self.phase == Phase::Idle && !self.pressed && !self.raw
+    }
+}
+
+impl Default for ButtonState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Time elapsed from `since_ms` to `now_ms`, robust to the counter wrapping around.
+fn elapsed(since_ms: u32, now_ms: u32) -> u32 {
+    now_ms.wrapping_sub(since_ms)
+}
+
+/// Feed a new sample of the button level (`true` when pressed) taken at `now_ms`.
+/// Returns the updated state and the event detected by this sample, if any.
+pub fn update(
+    state: ButtonState,
+    timings: &Timings,
+    raw: bool,
+    now_ms: u32,
+) -> (ButtonState, Option<ButtonEvent>) {
+    let mut state = state;
+
+    // Debouncing
+    if raw != state.raw {
+        state.raw = raw;
+        state.raw_since_ms = now_ms;
+    }
+    let was_pressed = state.pressed;
+    if elapsed(state.raw_since_ms, now_ms) >= timings.debounce_ms {
+        state.pressed = state.raw;
+    }
+    let pressed_now = state.pressed && !was_pressed;
+    let released_now = !state.pressed && was_pressed;
+
+    let mut event = None;
+    state.phase = match state.phase {
+        Phase::Idle if pressed_now => {
+            event = Some(ButtonEvent::Pressed);
+            Phase::Pressed { since_ms: now_ms, next_event_ms: now_ms.wrapping_add(timings.long_press_ms), long: false }
+        }
+
+        Phase::Pressed { long, .. } if released_now => {
+            event = Some(ButtonEvent::Released);
+            if long {
+                Phase::Idle
+            } else {
+                Phase::Released { since_ms: now_ms }
+            }
+        }
+
+        Phase::Pressed { since_ms, next_event_ms, long } => {
+            // wrap-safe "now_ms >= next_event_ms", as both are after since_ms
+            if elapsed(since_ms, now_ms) >= elapsed(since_ms, next_event_ms) && (!long || timings.repeat_ms > 0) {
+                event = Some(if long { ButtonEvent::Held } else { ButtonEvent::LongPress });
+                Phase::Pressed { since_ms, next_event_ms: next_event_ms.wrapping_add(timings.repeat_ms), long: true }
+            } else {
+                state.phase
+            }
+        }
+
+        Phase::Released { .. } if pressed_now && timings.double_click_ms > 0 => {
+            event = Some(ButtonEvent::DoubleClick);
+            Phase::SecondPress
+        }
+
+        Phase::Released { since_ms } => {
+            if elapsed(since_ms, now_ms) >= timings.double_click_ms {
+                event = Some(ButtonEvent::ShortPress);
+                Phase::Idle
+            } else {
+                state.phase
+            }
+        }
+
+        Phase::SecondPress if released_now => {
+            event = Some(ButtonEvent::Released);
+            Phase::Idle
+        }
+
+        phase => phase,
+    };
+
+    (state, event)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Button sampled every millisecond, recording the detected events with their time.
+    struct Sampled {
+        state: ButtonState,
+        timings: Timings,
+        now_ms: u32,
+        events: Vec<(u32, ButtonEvent)>,
+    }
+
+    impl Sampled {
+        fn new() -> Self {
+            Sampled::starting_at(0)
+        }
+
+        fn starting_at(now_ms: u32) -> Self {
+            Sampled { state: ButtonState::new(), timings: Timings::default(), now_ms, events: Vec::new() }
+        }
+
+        /// Keep the raw level for the given milliseconds.
+        fn level(&mut self, pressed: bool, ms: u32) -> &mut Self {
+            for _ in 0..ms {
+                let (state, event) = update(self.state, &self.timings, pressed, self.now_ms);
+                self.state = state;
+                self.events.extend(event.map(|event| (self.now_ms, event)));
+                self.now_ms = self.now_ms.wrapping_add(1);
+            }
+            self
+        }
+
+        fn press(&mut self, ms: u32) -> &mut Self {
+            self.level(true, ms)
+        }
+
+        fn release(&mut self, ms: u32) -> &mut Self {
+            self.level(false, ms)
+        }
+
+        /// Contact bouncing for the given milliseconds, toggling every 3 ms.
+        fn bounce(&mut self, ms: u32) -> &mut Self {
+            for toggle in 0..ms / 3 {
+                self.level(toggle % 2 == 0, 3);
+            }
+            self
+        }
+
+        fn kinds(&self) -> Vec<ButtonEvent> {
+            self.events.iter().map(|(_, event)| *event).collect()
+        }
+    }
+
+    #[test]
+    fn short_press_waits_for_the_double_click_time() {
+        let mut button = Sampled::new();
+        button.press(100).release(1000);
+        // Debounced 20 ms after each edge
+        assert_eq!(button.events, [
+            (20, ButtonEvent::Pressed),
+            (120, ButtonEvent::Released),
+            (370, ButtonEvent::ShortPress),
+        ]);
+    }
+
+    #[test]
+    fn bounce_on_press_counts_once() {
+        let mut button = Sampled::new();
+        button.bounce(15).press(100).release(1000);
+        assert_eq!(button.kinds(), [ButtonEvent::Pressed, ButtonEvent::Released, ButtonEvent::ShortPress]);
+    }
+
+    #[test]
+    fn bounce_on_release_counts_once() {
+        let mut button = Sampled::new();
+        button.press(100).bounce(18).release(1000);
+        assert_eq!(button.kinds(), [ButtonEvent::Pressed, ButtonEvent::Released, ButtonEvent::ShortPress]);
+    }
+
+    #[test]
+    fn glitches_shorter_than_the_debounce_are_ignored() {
+        let mut button = Sampled::new();
+        button.release(50).press(19).release(1000);
+        assert!(button.events.is_empty());
+    }
+
+    #[test]
+    fn long_press_at_the_threshold_then_held() {
+        let mut button = Sampled::new();
+        // Pressed from 20 ms on, once debounced
+        button.press(20 + 800);
+        assert_eq!(button.events, [(20, ButtonEvent::Pressed)]);
+        button.press(1);
+        assert_eq!(button.events[1..], [(820, ButtonEvent::LongPress)]);
+
+        button.press(400).release(1000);
+        assert_eq!(button.events, [
+            (20, ButtonEvent::Pressed),
+            (820, ButtonEvent::LongPress),
+            (1020, ButtonEvent::Held),
+            (1220, ButtonEvent::Held),
+            (1241, ButtonEvent::Released),
+        ]);
+    }
+
+    #[test]
+    fn click_or_long_press_is_decided_by_the_press_length() {
+        // Both edges are debounced alike, so the press lasts as long as the raw one
+        let mut click = Sampled::new();
+        click.press(800).release(1000);
+        assert_eq!(click.kinds(), [ButtonEvent::Pressed, ButtonEvent::Released, ButtonEvent::ShortPress]);
+
+        let mut long = Sampled::new();
+        long.press(801).release(1000);
+        assert_eq!(long.kinds(), [ButtonEvent::Pressed, ButtonEvent::LongPress, ButtonEvent::Released]);
+    }
+
+    #[test]
+    fn second_press_makes_a_double_click() {
+        let mut button = Sampled::new();
+        button.press(100).release(200).press(100).release(1000);
+        // The second press is reported as the double click
+        assert_eq!(button.kinds(), [
+            ButtonEvent::Pressed,
+            ButtonEvent::Released,
+            ButtonEvent::DoubleClick,
+            ButtonEvent::Released,
+        ]);
+
+        // Too late for a double click
+        let mut button = Sampled::new();
+        button.press(100).release(300).press(100).release(1000);
+        assert_eq!(button.kinds(), [
+            ButtonEvent::Pressed,
+            ButtonEvent::Released,
+            ButtonEvent::ShortPress,
+            ButtonEvent::Pressed,
+            ButtonEvent::Released,
+            ButtonEvent::ShortPress,
+        ]);
+    }
+
+    #[test]
+    fn double_clicks_and_repeat_can_be_disabled() {
+        let mut button = Sampled::new();
+        button.timings = Timings { double_click_ms: 0, repeat_ms: 0, ..Timings::default() };
+        button.press(100).release(30);
+        // Reported right away
+        assert_eq!(button.events[1..], [(120, ButtonEvent::Released), (121, ButtonEvent::ShortPress)]);
+
+        button.press(2000).release(30);
+        assert_eq!(button.kinds()[3..], [ButtonEvent::Pressed, ButtonEvent::LongPress, ButtonEvent::Released]);
+    }
+
+    #[test]
+    fn idle_once_the_click_is_reported() {
+        let mut button = Sampled::new();
+        assert!(button.state.is_idle());
+        // Not even debounced yet
+        button.press(1);
+        assert!(!button.state.is_idle());
+        button.press(100).release(20);
+        assert!(!button.state.is_idle());
+        // Waiting for a second press
+        button.release(1);
+        assert!(!button.state.is_idle());
+        button.release(249);
+        assert!(!button.state.is_idle());
+        button.release(1);
+        assert_eq!(button.kinds(), [ButtonEvent::Pressed, ButtonEvent::Released, ButtonEvent::ShortPress]);
+        assert!(button.state.is_idle());
+
+        // Back to idle after a glitch too
+        button.press(5).release(1);
+        assert!(button.state.is_idle());
+    }
+
+    #[test]
+    fn timings_survive_the_counter_wrapping() {
+        let mut button = Sampled::starting_at(u32::MAX - 500);
+        button.press(1100).release(1000);
+        assert_eq!(button.kinds(), [
+            ButtonEvent::Pressed,
+            ButtonEvent::LongPress,
+            ButtonEvent::Held,
+            ButtonEvent::Released,
+        ]);
+        assert_eq!(button.events[1].0, (u32::MAX - 500).wrapping_add(820));
+    }
+}
@@ -0,0 +1,9 @@
+//! Clock
+//! Wall-clock dates and times, as kept by the RTC.
+
+pub use calendar::DateTime;
+
+pub mod calendar;
+
+/// Years the RTC can count (it only stores two digits).
+pub const YEARS: (u16, u16) = (2000, 2099);
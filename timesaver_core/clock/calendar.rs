@@ -0,0 +1,236 @@
+//! Gregorian calendar arithmetic, free of any hardware dependency.
+
+use crate::encoder_interface::range::{self, Policy};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+    Monday = 1,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    /// Weekday numbered from 1 (Monday) to 7 (Sunday), as in ISO 8601 and in the RTC registers.
+    pub fn from_number(number: u8) -> Option<Weekday> {
+        const WEEKDAYS: [Weekday; 7] = [
+            Weekday::Monday,
+            Weekday::Tuesday,
+            Weekday::Wednesday,
+            Weekday::Thursday,
+            Weekday::Friday,
+            Weekday::Saturday,
+            Weekday::Sunday,
+        ];
+        WEEKDAYS.get((number as usize).checked_sub(1)?).copied()
+    }
+}
+
+/// Fields of a `DateTime` that can be edited one at a time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Year,
+    Month,
+    Day,
+    Hour,
+    Minute,
+}
+
+impl Field {
+    /// Next field to edit, from the largest unit to the smallest, or None after the last one.
+    pub fn next(self) -> Option<Field> {
+        match self {
+            Field::Year => Some(Field::Month),
+            Field::Month => Some(Field::Day),
+            Field::Day => Some(Field::Hour),
+            Field::Hour => Some(Field::Minute),
+            Field::Minute => None,
+        }
+    }
+}
+
+/// Date and time of day.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    /// 1 to 12.
+    pub month: u8,
+    /// 1 to the days in the month.
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+pub fn is_leap_year(year: u16) -> bool {
+    (year.is_multiple_of(4) && !year.is_multiple_of(100)) || year.is_multiple_of(400)
+}
+
+/// Days in the given month (1 to 12) of the given year, 0 for invalid months.
+pub fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 0,
+    }
+}
+
+/// Day of the week of a valid date, through Sakamoto's method.
+pub fn day_of_week(year: u16, month: u8, day: u8) -> Weekday {
+    const MONTH_OFFSETS: [u32; 12] = [0, 3, 2, 5, 0, 3, 5, 1, 4, 6, 2, 4];
+    // January and February count as months of the previous year
+    let year = if month < 3 { (year as u32).saturating_sub(1) } else { year as u32 };
+    let sunday_based =
+        (year + year / 4 - year / 100 + year / 400 + MONTH_OFFSETS[(month as usize - 1) % 12] + day as u32) % 7;
+    // 0 is Sunday
+    Weekday::from_number(((sunday_based + 6) % 7 + 1) as u8).unwrap_or(Weekday::Monday)
+}
+
+impl DateTime {
+    pub const fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Self {
+        DateTime { year, month, day, hour, minute, second }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+
+    pub fn weekday(&self) -> Weekday {
+        day_of_week(self.year, self.month, self.day)
+    }
+
+    /// Seconds from this time of day to the next `hour:minute`, 0 if it is exactly now.
+    pub fn seconds_until(&self, hour: u8, minute: u8) -> u32 {
+        const SECONDS_PER_DAY: i32 = 24 * 60 * 60;
+        let seconds_of_day = |hour: u8, minute: u8, second: u8| (hour as i32 * 60 + minute as i32) * 60 + second as i32;
+        let seconds = seconds_of_day(hour, minute, 0) - seconds_of_day(self.hour, self.minute, self.second);
+        seconds.rem_euclid(SECONDS_PER_DAY) as u32
+    }
+
+    /// Move a field by the given steps, wrapping around its range (years within `years`), and keeping the day
+    /// within the month; the other fields are unchanged, e.g. minutes do not carry into hours.
+    pub fn adjust(&self, field: Field, steps: i32, years: (u16, u16)) -> DateTime {
+        let wrap = |min: i32, max: i32, value: i32| range::apply(&Policy::wrap(min, max, 1), value, steps);
+        let mut adjusted = *self;
+        match field {
+            Field::Year => adjusted.year = wrap(years.0 as i32, years.1 as i32, self.year as i32) as u16,
+            Field::Month => adjusted.month = wrap(1, 12, self.month as i32) as u8,
+            Field::Day => adjusted.day = wrap(1, days_in_month(self.year, self.month) as i32, self.day as i32) as u8,
+            Field::Hour => adjusted.hour = wrap(0, 23, self.hour as i32) as u8,
+            Field::Minute => adjusted.minute = wrap(0, 59, self.minute as i32) as u8,
+        }
+        adjusted.day = adjusted.day.clamp(1, days_in_month(adjusted.year, adjusted.month).max(1));
+        adjusted
+    }
+
+    /// Parse `YYYY-MM-DD HH:MM` or `YYYY-MM-DD HH:MM:SS`, if valid.
+    pub fn parse(text: &str) -> Option<DateTime> {
+        let (date, time) = text.trim().split_once(' ')?;
+
+        let mut date = date.splitn(3, '-');
+        let year = date.next()?.parse().ok()?;
+        let month = date.next()?.parse().ok()?;
+        let day = date.next()?.parse().ok()?;
+
+        let mut time = time.trim().splitn(3, ':');
+        let hour = time.next()?.parse().ok()?;
+        let minute = time.next()?.parse().ok()?;
+        let second = time.next().map_or(Some(0), |second| second.parse().ok())?;
+
+        let date_time = DateTime::new(year, month, day, hour, minute, second);
+        date_time.is_valid().then_some(date_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YEARS: (u16, u16) = crate::clock::YEARS;
+
+    #[test]
+    fn leap_years() {
+        assert!(is_leap_year(2024));
+        assert!(!is_leap_year(2023));
+        assert!(!is_leap_year(2100));
+        assert!(is_leap_year(2000));
+        assert_eq!(days_in_month(2024, 2), 29);
+        assert_eq!(days_in_month(2023, 2), 28);
+        assert_eq!(days_in_month(2023, 4), 30);
+        assert_eq!(days_in_month(2023, 12), 31);
+        assert_eq!(days_in_month(2023, 0), 0);
+        assert_eq!(days_in_month(2023, 13), 0);
+    }
+
+    #[test]
+    fn weekdays() {
+        assert_eq!(day_of_week(2000, 1, 1), Weekday::Saturday);
+        assert_eq!(day_of_week(2024, 2, 29), Weekday::Thursday);
+        assert_eq!(day_of_week(2024, 3, 4), Weekday::Monday);
+        assert_eq!(day_of_week(2099, 12, 31), Weekday::Thursday);
+        assert_eq!(Weekday::from_number(0), None);
+        assert_eq!(Weekday::from_number(7), Some(Weekday::Sunday));
+        assert_eq!(Weekday::from_number(8), None);
+    }
+
+    #[test]
+    fn days_are_clamped_to_the_month() {
+        let january_31 = DateTime::new(2023, 1, 31, 12, 0, 0);
+        assert_eq!(january_31.adjust(Field::Month, 1, YEARS), DateTime::new(2023, 2, 28, 12, 0, 0));
+        assert_eq!(january_31.adjust(Field::Month, 3, YEARS), DateTime::new(2023, 4, 30, 12, 0, 0));
+
+        let leap_day = DateTime::new(2024, 2, 29, 12, 0, 0);
+        assert_eq!(leap_day.adjust(Field::Year, 1, YEARS), DateTime::new(2025, 2, 28, 12, 0, 0));
+        assert_eq!(leap_day.adjust(Field::Year, 4, YEARS).day, 29);
+    }
+
+    #[test]
+    fn fields_roll_over_without_carrying() {
+        let date_time = DateTime::new(2099, 12, 31, 23, 59, 30);
+        assert_eq!(date_time.adjust(Field::Minute, 1, YEARS), DateTime::new(2099, 12, 31, 23, 0, 30));
+        assert_eq!(date_time.adjust(Field::Hour, 1, YEARS), DateTime::new(2099, 12, 31, 0, 59, 30));
+        assert_eq!(date_time.adjust(Field::Day, 1, YEARS), DateTime::new(2099, 12, 1, 23, 59, 30));
+        assert_eq!(date_time.adjust(Field::Month, 1, YEARS), DateTime::new(2099, 1, 31, 23, 59, 30));
+        assert_eq!(date_time.adjust(Field::Year, 1, YEARS), DateTime::new(2000, 12, 31, 23, 59, 30));
+
+        let date_time = DateTime::new(2000, 1, 1, 0, 0, 0);
+        assert_eq!(date_time.adjust(Field::Minute, -1, YEARS).minute, 59);
+        assert_eq!(date_time.adjust(Field::Hour, -1, YEARS).hour, 23);
+        assert_eq!(date_time.adjust(Field::Day, -1, YEARS).day, 31);
+        assert_eq!(date_time.adjust(Field::Month, -1, YEARS).month, 12);
+        assert_eq!(date_time.adjust(Field::Year, -1, YEARS).year, 2099);
+
+        // February of a leap year has 29 days to go through
+        let february = DateTime::new(2024, 2, 28, 0, 0, 0);
+        assert_eq!(february.adjust(Field::Day, 1, YEARS).day, 29);
+        assert_eq!(february.adjust(Field::Day, 2, YEARS).day, 1);
+    }
+
+    #[test]
+    fn seconds_until_the_next_time_of_day() {
+        let date_time = DateTime::new(2023, 6, 1, 7, 30, 15);
+        assert_eq!(date_time.seconds_until(7, 31), 45);
+        assert_eq!(date_time.seconds_until(8, 0), 29 * 60 + 45);
+        assert_eq!(date_time.seconds_until(7, 30), 24 * 60 * 60 - 15);
+        assert_eq!(DateTime::new(2023, 6, 1, 7, 30, 0).seconds_until(7, 30), 0);
+    }
+
+    #[test]
+    fn parse_valid_dates_only() {
+        assert_eq!(DateTime::parse("2024-02-29 13:05"), Some(DateTime::new(2024, 2, 29, 13, 5, 0)));
+        assert_eq!(DateTime::parse(" 2024-02-29  13:05:09 "), Some(DateTime::new(2024, 2, 29, 13, 5, 9)));
+        assert_eq!(DateTime::parse("2023-02-29 13:05"), None);
+        assert_eq!(DateTime::parse("2023-02-28 24:00"), None);
+        assert_eq!(DateTime::parse("2023-02-28"), None);
+        assert_eq!(DateTime::parse("2023-02-28 12:00:xx"), None);
+    }
+}
@@ -0,0 +1,163 @@
+//! Config
+//! Device configuration and its layout in storage, versioned and checksummed so that the firmware can tell
+//! stored configurations apart from erased or corrupted storage.
+
+use crate::encoder_interface::decoder::{EncoderConfig, Resolution};
+
+/// Marks a stored configuration, telling it apart from an erased (all 0xff) or never written sector.
+const MAGIC: [u8; 4] = *b"TSCF";
+/// Layout version of the stored configuration, to be increased whenever `encode` changes.
+const VERSION: u8 = 2;
+
+/// Length of an encoded configuration.
+pub const CONFIG_LEN: usize = 16;
+
+/// Length of configurations stored by version 1, without the clock drift, still read as such.
+const V1_LEN: usize = 12;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeviceConfig {
+    /// Decoding of the knob, depending on the model fitted and on how it is mounted.
+    pub encoder: EncoderConfig,
+    /// Drift of the timers against the LSE or a reference, in ppm (see `millis::calibration`).
+    pub clock_ppm: i32,
+}
+
+impl DeviceConfig {
+    pub const DEFAULT: DeviceConfig = DeviceConfig { encoder: EncoderConfig::DEFAULT, clock_ppm: 0 };
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Fletcher-16 checksum.
+fn checksum(bytes: &[u8]) -> u16 {
+    let (sum1, sum2) = bytes.iter().fold((0u16, 0u16), |(sum1, sum2), &byte| {
+        let sum1 = (sum1 + byte as u16) % 255;
+        (sum1, (sum2 + sum1) % 255)
+    });
+    (sum2 << 8) | sum1
+}
+
+/// Serialise a configuration: magic, version, resolution, inversion, a spare byte, detents per revolution,
+/// clock drift and checksum (multi-byte fields are little endian).
+pub fn encode(config: &DeviceConfig) -> [u8; CONFIG_LEN] {
+    let mut bytes = [0u8; CONFIG_LEN];
+    bytes[0..4].copy_from_slice(&MAGIC);
+    bytes[4] = VERSION;
+    bytes[5] = match config.encoder.resolution {
+        Resolution::Full => 0,
+        Resolution::Half => 1,
+        Resolution::Quarter => 2,
+    };
+    bytes[6] = config.encoder.inverted as u8;
+    bytes[8..10].copy_from_slice(&config.encoder.detents_per_revolution.to_le_bytes());
+    bytes[10..14].copy_from_slice(&config.clock_ppm.to_le_bytes());
+    let checksum = checksum(&bytes[..CONFIG_LEN - 2]);
+    bytes[CONFIG_LEN - 2..].copy_from_slice(&checksum.to_le_bytes());
+    bytes
+}
+
+/// Deserialise a configuration, if valid. Version 1 ones get no clock drift.
+pub fn decode(bytes: &[u8; CONFIG_LEN]) -> Option<DeviceConfig> {
+    let len = match bytes[4] {
+        1 => V1_LEN,
+        VERSION => CONFIG_LEN,
+        _ => return None,
+    };
+    let stored_checksum = u16::from_le_bytes([bytes[len - 2], bytes[len - 1]]);
+    if bytes[0..4] != MAGIC || checksum(&bytes[..len - 2]) != stored_checksum {
+        return None;
+    }
+
+    let resolution = match bytes[5] {
+        0 => Resolution::Full,
+        1 => Resolution::Half,
+        2 => Resolution::Quarter,
+        _ => return None,
+    };
+    Some(DeviceConfig {
+        encoder: EncoderConfig {
+            resolution,
+            inverted: bytes[6] != 0,
+            detents_per_revolution: u16::from_le_bytes([bytes[8], bytes[9]]),
+        },
+        clock_ppm: match len {
+            V1_LEN => 0,
+            _ => i32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: DeviceConfig = DeviceConfig {
+        encoder: EncoderConfig { resolution: Resolution::Quarter, inverted: true, detents_per_revolution: 0x0118 },
+        clock_ppm: -120,
+    };
+
+    /// Configuration stored by version 1, followed by erased flash.
+    fn v1_bytes(resolution: u8, inverted: u8, detents: u16) -> [u8; CONFIG_LEN] {
+        let mut bytes = [0xff; CONFIG_LEN];
+        bytes[0..4].copy_from_slice(b"TSCF");
+        bytes[4..8].copy_from_slice(&[1, resolution, inverted, 0]);
+        bytes[8..10].copy_from_slice(&detents.to_le_bytes());
+        let checksum = checksum(&bytes[..V1_LEN - 2]);
+        bytes[V1_LEN - 2..V1_LEN].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn checksum_is_fletcher_16() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"abcde"), 0xc8f0);
+        assert_eq!(checksum(b"abcdef"), 0x2057);
+        assert_eq!(checksum(b"abcdefgh"), 0x0627);
+        // Sums are modulo 255, not 256
+        assert_eq!(checksum(&[0xff; 4]), 0);
+    }
+
+    #[test]
+    fn version_2_layout() {
+        let bytes = encode(&CONFIG);
+        assert_eq!(bytes[..14], [b'T', b'S', b'C', b'F', 2, 2, 1, 0, 0x18, 0x01, 0x88, 0xff, 0xff, 0xff]);
+        assert_eq!(u16::from_le_bytes([bytes[14], bytes[15]]), checksum(&bytes[..14]));
+        assert_eq!(decode(&bytes), Some(CONFIG));
+        assert_eq!(decode(&encode(&DeviceConfig::DEFAULT)), Some(DeviceConfig::DEFAULT));
+    }
+
+    #[test]
+    fn version_1_is_read_without_clock_drift() {
+        let config = decode(&v1_bytes(1, 0, 24)).unwrap();
+        let encoder = EncoderConfig { resolution: Resolution::Half, inverted: false, detents_per_revolution: 24 };
+        assert_eq!(config, DeviceConfig { encoder, clock_ppm: 0 });
+
+        // Stored again with the current layout, which keeps it
+        let migrated = encode(&config);
+        assert_eq!(migrated[4], VERSION);
+        assert_eq!(decode(&migrated), Some(config));
+    }
+
+    #[test]
+    fn corrupted_configurations_are_rejected() {
+        let bytes = encode(&CONFIG);
+        for (i, byte) in [(0, b'X'), (3, b'f'), (4, 3), (4, 0), (10, 0x89), (15, 0)] {
+            let mut corrupted = bytes;
+            corrupted[i] = byte;
+            assert_eq!(decode(&corrupted), None, "byte {} set to {:#x}", i, byte);
+        }
+        // Erased flash, and resolutions decoding does not know
+        assert_eq!(decode(&[0xff; CONFIG_LEN]), None);
+        assert_eq!(decode(&[0; CONFIG_LEN]), None);
+        assert_eq!(decode(&v1_bytes(3, 0, 20)), None);
+
+        let mut corrupted = v1_bytes(0, 1, 20);
+        corrupted[9] ^= 1;
+        assert_eq!(decode(&corrupted), None);
+    }
+}
@@ -0,0 +1,6 @@
+//! Encoder interface
+//! Decoding of rotary encoders, from the levels of their pins to the values they set.
+
+pub mod acceleration;
+pub mod decoder;
+pub mod range;
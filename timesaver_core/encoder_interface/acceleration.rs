@@ -0,0 +1,162 @@
+//! Velocity-based acceleration: the faster the knob spins, the bigger the step of each detent.
+//! Free of any hardware dependency, it is fed with timestamped direction events.
+
+/// Step curve, mapping the time elapsed between two consecutive detents to a step size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    /// Every detent is a single step.
+    Linear,
+    /// `(max_interval_ms, step)` pairs, sorted by increasing interval: the first pair whose interval is not
+    /// shorter than the elapsed time gives the step, otherwise it is 1.
+    Thresholds(&'static [(u32, i32)]),
+    /// Step doubling every `doubling_ms` below `slow_ms`, up to `max_step`.
+    Exponential { slow_ms: u32, doubling_ms: u32, max_step: i32 },
+}
+
+/// A reasonable curve to select minutes: 10 minutes per detent when flicking the knob.
+pub const DEFAULT_CURVE: Curve = Curve::Thresholds(&[(25, 10), (50, 5), (100, 2)]);
+
+/// Steps per revolution the curves are meant for, i.e. of a full-step 20-detent encoder. Intervals between the
+/// steps of other encoders are rescaled to it, so that the acceleration follows how fast the knob turns.
+pub const REFERENCE_STEPS_PER_REVOLUTION: u32 = 20;
+
+/// Interval between two steps of an encoder with the given steps per revolution, as if it had the reference ones.
+pub fn rescale(elapsed_ms: u32, steps_per_revolution: u32) -> u32 {
+    let rescaled = elapsed_ms as u64 * steps_per_revolution.max(1) as u64 / REFERENCE_STEPS_PER_REVOLUTION as u64;
+    rescaled.min(u32::MAX as u64) as u32
+}
+
+/// Last detent seen, needed to measure the rotation speed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AccelerationState {
+    last_detent: Option<(u32, i8)>,
+}
+
+impl AccelerationState {
+    pub const fn new() -> Self {
+        AccelerationState { last_detent: None }
+    }
+}
+
+/// Step size of a detent, given the time elapsed since the previous one.
+pub fn step(curve: &Curve, elapsed_ms: u32) -> i32 {
+    match *curve {
+        Curve::Linear => 1,
+        Curve::Thresholds(thresholds) => thresholds
+            .iter()
+            .find(|(max_interval_ms, _)| elapsed_ms <= *max_interval_ms)
+            .map_or(1, |(_, step)| *step),
+        Curve::Exponential { slow_ms, doubling_ms, max_step } => {
+            if elapsed_ms >= slow_ms {
+                1
+            } else {
+                let doublings = ((slow_ms - elapsed_ms) / doubling_ms.max(1)).min(30);
+                (1i32 << doublings).clamp(1, max_step.max(1))
+            }
+        }
+    }
+}
+
+/// Accelerate a detent in `direction` (+1 clockwise, -1 anticlockwise) happened at `now_ms`, on an encoder with
+/// the given steps per revolution. Returns the updated state and the signed step to apply. Reversing the
+/// direction always restarts from a single step, so that overshooting can be corrected precisely.
+pub fn accelerate(
+    state: AccelerationState,
+    curve: &Curve,
+    steps_per_revolution: u32,
+    now_ms: u32,
+    direction: i8,
+) -> (AccelerationState, i32) {
+    let step = match state.last_detent {
+        Some((last_ms, last_direction)) if last_direction == direction => {
+            step(curve, rescale(now_ms.wrapping_sub(last_ms), steps_per_revolution))
+        }
+        _ => 1,
+    };
+    let state = AccelerationState { last_detent: Some((now_ms, direction)) };
+    (state, step * direction.signum() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Steps of detents in `direction` at the given times, on a reference encoder.
+    fn steps(curve: &Curve, times_ms: &[u32], direction: i8) -> Vec<i32> {
+        steps_of(REFERENCE_STEPS_PER_REVOLUTION, curve, times_ms, direction)
+    }
+
+    fn steps_of(steps_per_revolution: u32, curve: &Curve, times_ms: &[u32], direction: i8) -> Vec<i32> {
+        let mut state = AccelerationState::new();
+        times_ms
+            .iter()
+            .map(|&now_ms| {
+                let (next, step) = accelerate(state, curve, steps_per_revolution, now_ms, direction);
+                state = next;
+                step
+            })
+            .collect()
+    }
+
+    #[test]
+    fn default_curve_follows_the_speed() {
+        let curve = DEFAULT_CURVE;
+        assert_eq!(step(&curve, 0), 10);
+        assert_eq!(step(&curve, 25), 10);
+        assert_eq!(step(&curve, 26), 5);
+        assert_eq!(step(&curve, 50), 5);
+        assert_eq!(step(&curve, 51), 2);
+        assert_eq!(step(&curve, 100), 2);
+        assert_eq!(step(&curve, 101), 1);
+        assert_eq!(step(&curve, u32::MAX), 1);
+        assert_eq!(step(&Curve::Linear, 0), 1);
+    }
+
+    #[test]
+    fn exponential_curve_doubles_up_to_its_maximum() {
+        let curve = Curve::Exponential { slow_ms: 100, doubling_ms: 20, max_step: 8 };
+        assert_eq!(step(&curve, 100), 1);
+        assert_eq!(step(&curve, 81), 1);
+        assert_eq!(step(&curve, 80), 2);
+        assert_eq!(step(&curve, 60), 4);
+        assert_eq!(step(&curve, 40), 8);
+        assert_eq!(step(&curve, 0), 8);
+
+        // A zero doubling time must not divide by zero
+        let curve = Curve::Exponential { slow_ms: 100, doubling_ms: 0, max_step: i32::MAX };
+        assert_eq!(step(&curve, 0), 1 << 30);
+    }
+
+    #[test]
+    fn first_detent_and_detents_after_idle_are_single_steps() {
+        assert_eq!(steps(&DEFAULT_CURVE, &[1000, 1020, 1060, 1140, 1300, 1320], 1), [1, 10, 5, 2, 1, 10]);
+        assert_eq!(steps(&DEFAULT_CURVE, &[0, 10, 5000, 5010], -1), [-1, -10, -1, -10]);
+    }
+
+    #[test]
+    fn reversing_restarts_from_a_single_step() {
+        let curve = DEFAULT_CURVE;
+        let steps_per_revolution = REFERENCE_STEPS_PER_REVOLUTION;
+        let (state, _) = accelerate(AccelerationState::new(), &curve, steps_per_revolution, 0, 1);
+        let (state, forward) = accelerate(state, &curve, steps_per_revolution, 10, 1);
+        let (state, back) = accelerate(state, &curve, steps_per_revolution, 20, -1);
+        let (_, back_again) = accelerate(state, &curve, steps_per_revolution, 30, -1);
+        assert_eq!((forward, back, back_again), (10, -1, -10));
+    }
+
+    #[test]
+    fn intervals_follow_the_speed_of_the_knob() {
+        // Twice the steps in a turn, so twice as fast for the same interval between them
+        assert_eq!(rescale(25, 40), 50);
+        assert_eq!(rescale(u32::MAX, 80), u32::MAX);
+        assert_eq!(steps_of(40, &DEFAULT_CURVE, &[0, 25, 50, 100], 1), [1, 5, 5, 2]);
+        assert_eq!(steps_of(10, &DEFAULT_CURVE, &[0, 50, 150], 1), [1, 10, 5]);
+    }
+
+    #[test]
+    fn speed_survives_the_counter_wrapping() {
+        assert_eq!(steps(&DEFAULT_CURVE, &[u32::MAX - 10, 10], 1), [1, 10]);
+    }
+}
@@ -0,0 +1,255 @@
+//! Quadrature decoding through a state table, free of any hardware dependency.
+//! Levels are packed as `CLK << 1 | DT`; clockwise rotation is CLK leading DT: 00 -> 10 -> 11 -> 01 -> 00.
+
+/// Steps counted over a full quadrature cycle (4 transitions), to match the detents of the encoder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resolution {
+    /// One step per cycle, for encoders with a detent where both pins are high.
+    Full,
+    /// Two steps per cycle, for encoders with a detent where both pins are either high or low.
+    Half,
+    /// A step on every transition, for encoders without detents.
+    Quarter,
+}
+
+impl Resolution {
+    /// Steps counted over a full quadrature cycle.
+    pub const fn steps_per_cycle(self) -> u32 {
+        match self {
+            Resolution::Full => 1,
+            Resolution::Half => 2,
+            Resolution::Quarter => 4,
+        }
+    }
+}
+
+/// How an encoder is decoded, depending on its model and on how it is mounted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncoderConfig {
+    pub resolution: Resolution,
+    /// Swap clockwise and anticlockwise, for encoders mounted flipped.
+    pub inverted: bool,
+    /// Detents in a full turn of the knob (quadrature cycles, for encoders without detents).
+    pub detents_per_revolution: u16,
+}
+
+impl EncoderConfig {
+    /// A common 20-detent encoder, mounted straight.
+    pub const DEFAULT: EncoderConfig = EncoderConfig {
+        resolution: Resolution::Full,
+        inverted: false,
+        detents_per_revolution: 20,
+    };
+
+    /// Steps counted in a full turn of the knob, assuming the resolution matches the detents of the encoder:
+    /// quarter steps are 4 per detent (or per cycle), the other resolutions count each detent once.
+    pub const fn steps_per_revolution(&self) -> u32 {
+        match self.resolution {
+            Resolution::Quarter => self.detents_per_revolution as u32 * 4,
+            _ => self.detents_per_revolution as u32,
+        }
+    }
+
+    /// Apply the direction inversion to decoded steps.
+    pub fn orient(&self, steps: i32) -> i32 {
+        if self.inverted {
+            -steps
+        } else {
+            steps
+        }
+    }
+}
+
+/// A single field of an `EncoderConfig` to change, e.g. from a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncoderSetting {
+    Resolution(Resolution),
+    Inverted(bool),
+    /// Detents in a full turn, at least 1.
+    DetentsPerRevolution(u16),
+}
+
+impl EncoderConfig {
+    /// Configuration with the given setting changed.
+    pub fn with(self, setting: EncoderSetting) -> EncoderConfig {
+        match setting {
+            EncoderSetting::Resolution(resolution) => EncoderConfig { resolution, ..self },
+            EncoderSetting::Inverted(inverted) => EncoderConfig { inverted, ..self },
+            EncoderSetting::DetentsPerRevolution(detents) => {
+                EncoderConfig { detents_per_revolution: detents.max(1), ..self }
+            }
+        }
+    }
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Quarter steps for each `previous << 2 | current` transition.
+/// Unchanged levels and invalid jumps (both pins toggled, e.g. a missed sample) count as no movement.
+const QUARTER_STEPS: [i8; 16] = [
+    0, -1, 1, 0, //
+    1, 0, 0, -1, //
+    -1, 0, 0, 1, //
+    0, 1, -1, 0, //
+];
+
+/// Levels where the knob rests between detents (both pins pulled up).
+const REST_LEVELS: u8 = 0b11;
+
+/// Levels halfway through a cycle, where half-step encoders have a detent too.
+const HALF_LEVELS: u8 = 0b00;
+
+/// Decoder status between two samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecoderState {
+    /// Last sampled levels.
+    levels: u8,
+    /// Quarter steps accumulated since the last detent.
+    steps: i8,
+}
+
+impl DecoderState {
+    pub const fn new() -> Self {
+        DecoderState { levels: REST_LEVELS, steps: 0 }
+    }
+
+    /// Feed a new sample, returning the steps completed by it.
+    pub fn update(&mut self, resolution: Resolution, dt: bool, clk: bool) -> i8 {
+        let (state, detents) = decode(*self, resolution, dt, clk);
+        *self = state;
+        detents
+    }
+}
+
+impl Default for DecoderState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Decode a new sample of DT and CLK levels at the given resolution.
+/// Returns the updated state and the completed steps: +1 clockwise, -1 anticlockwise, 0 otherwise.
+/// With detents, a step is counted when the knob gets back to rest after moving at least half-way in one
+/// direction, so that a bouncing contact never counts twice.
+pub fn decode(state: DecoderState, resolution: Resolution, dt: bool, clk: bool) -> (DecoderState, i8) {
+    let levels = (clk as u8) << 1 | dt as u8;
+    let steps = state.steps + QUARTER_STEPS[(state.levels << 2 | levels) as usize];
+
+    let (at_rest, threshold) = match resolution {
+        Resolution::Full => (levels == REST_LEVELS, 2),
+        Resolution::Half => (levels == REST_LEVELS || levels == HALF_LEVELS, 1),
+        Resolution::Quarter => (true, 1),
+    };
+
+    if at_rest {
+        let detents = match steps {
+            s if s >= threshold => 1,
+            s if s <= -threshold => -1,
+            _ => 0,
+        };
+        (DecoderState { levels, steps: 0 }, detents)
+    } else {
+        (DecoderState { levels, steps: steps.clamp(-4, 4) }, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// A clockwise quadrature cycle, as `CLK << 1 | DT` levels, from rest back to rest.
+    const CLOCKWISE: [u8; 4] = [0b01, 0b00, 0b10, 0b11];
+    const ANTICLOCKWISE: [u8; 4] = [0b10, 0b00, 0b01, 0b11];
+
+    /// Steps completed by each sample of a recorded sequence of levels, starting from rest.
+    fn decode_all(resolution: Resolution, levels: &[u8]) -> Vec<i8> {
+        let mut state = DecoderState::new();
+        levels.iter().map(|&levels| state.update(resolution, levels & 0b01 != 0, levels & 0b10 != 0)).collect()
+    }
+
+    fn total(resolution: Resolution, levels: &[u8]) -> i32 {
+        decode_all(resolution, levels).iter().map(|&steps| steps as i32).sum()
+    }
+
+    #[test]
+    fn full_detents_count_once_back_at_rest() {
+        assert_eq!(decode_all(Resolution::Full, &CLOCKWISE), [0, 0, 0, 1]);
+        assert_eq!(decode_all(Resolution::Full, &ANTICLOCKWISE), [0, 0, 0, -1]);
+        assert_eq!(total(Resolution::Full, &[CLOCKWISE, CLOCKWISE, CLOCKWISE].concat()), 3);
+        assert_eq!(total(Resolution::Full, &[CLOCKWISE, ANTICLOCKWISE].concat()), 0);
+    }
+
+    #[test]
+    fn contact_bounce_never_counts_twice() {
+        // Bouncing while leaving the detent, halfway, and while reaching the next one
+        let bouncing = [0b01, 0b11, 0b01, 0b11, 0b01, 0b00, 0b01, 0b00, 0b10, 0b11, 0b10, 0b11];
+        assert_eq!(total(Resolution::Full, &bouncing), 1);
+        assert_eq!(decode_all(Resolution::Full, &bouncing).iter().filter(|&&steps| steps != 0).count(), 1);
+    }
+
+    #[test]
+    fn wiggling_without_leaving_the_detent_counts_nothing() {
+        assert_eq!(total(Resolution::Full, &[0b01, 0b11, 0b10, 0b11, 0b01, 0b11]), 0);
+        // Turned back before halfway
+        assert_eq!(total(Resolution::Full, &[0b01, 0b00, 0b01, 0b11]), 0);
+    }
+
+    #[test]
+    fn skipped_state_still_counts_the_detent() {
+        // The 00 levels of the cycle were missed, e.g. by a late sample: the jump counts as no movement, while
+        // the other transitions are enough to tell the direction
+        assert_eq!(total(Resolution::Full, &[0b01, 0b10, 0b11]), 1);
+        assert_eq!(total(Resolution::Full, &[0b10, 0b01, 0b11]), -1);
+        // Too many samples missed to know
+        assert_eq!(total(Resolution::Full, &[0b00, 0b11]), 0);
+    }
+
+    #[test]
+    fn half_steps_count_at_both_detents() {
+        assert_eq!(decode_all(Resolution::Half, &CLOCKWISE), [0, 1, 0, 1]);
+        assert_eq!(decode_all(Resolution::Half, &ANTICLOCKWISE), [0, -1, 0, -1]);
+        // Bouncing on the middle detent
+        assert_eq!(total(Resolution::Half, &[0b01, 0b00, 0b01, 0b00, 0b10, 0b11]), 2);
+    }
+
+    #[test]
+    fn quarter_steps_count_every_transition() {
+        assert_eq!(decode_all(Resolution::Quarter, &CLOCKWISE), [1, 1, 1, 1]);
+        assert_eq!(decode_all(Resolution::Quarter, &ANTICLOCKWISE), [-1, -1, -1, -1]);
+        // Bouncing goes back and forth, and skipped states are lost
+        assert_eq!(decode_all(Resolution::Quarter, &[0b01, 0b11, 0b01, 0b10]), [1, -1, 1, 0]);
+    }
+
+    #[test]
+    fn resolutions_match_their_steps_per_cycle() {
+        for resolution in [Resolution::Full, Resolution::Half, Resolution::Quarter] {
+            assert_eq!(total(resolution, &CLOCKWISE), resolution.steps_per_cycle() as i32);
+            assert_eq!(total(resolution, &ANTICLOCKWISE), -(resolution.steps_per_cycle() as i32));
+        }
+    }
+
+    #[test]
+    fn orientation() {
+        assert_eq!(EncoderConfig::DEFAULT.orient(3), 3);
+        assert_eq!(EncoderConfig { inverted: true, ..EncoderConfig::DEFAULT }.orient(3), -3);
+    }
+
+    #[test]
+    fn settings_change_a_single_field() {
+        let config = EncoderConfig::DEFAULT.with(EncoderSetting::Resolution(Resolution::Quarter));
+        assert_eq!(config, EncoderConfig { resolution: Resolution::Quarter, ..EncoderConfig::DEFAULT });
+        assert_eq!(config.steps_per_revolution(), 80);
+
+        let config = config.with(EncoderSetting::DetentsPerRevolution(24)).with(EncoderSetting::Inverted(true));
+        let expected = EncoderConfig { resolution: Resolution::Quarter, inverted: true, detents_per_revolution: 24 };
+        assert_eq!(config, expected);
+        assert_eq!(config.with(EncoderSetting::Resolution(Resolution::Half)).steps_per_revolution(), 24);
+        assert_eq!(config.with(EncoderSetting::DetentsPerRevolution(0)).detents_per_revolution, 1);
+    }
+}
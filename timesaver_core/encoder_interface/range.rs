@@ -0,0 +1,128 @@
+//! Range policies of encoder values, free of any hardware dependency.
+
+/// What happens when the value reaches its bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Range {
+    /// Any i32 value, saturating at the type bounds instead of overflowing.
+    Unbounded,
+    /// Values in [min, max], stopping at the bounds.
+    Clamp { min: i32, max: i32 },
+    /// Values in [min, max], going around from one bound to the other (e.g. menu entries).
+    Wrap { min: i32, max: i32 },
+}
+
+/// How an encoder value evolves: range, and amount added by each step.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Policy {
+    pub range: Range,
+    pub step: i32,
+}
+
+impl Policy {
+    pub const UNBOUNDED: Policy = Policy { range: Range::Unbounded, step: 1 };
+
+    pub const fn clamp(min: i32, max: i32, step: i32) -> Self {
+        Policy { range: Range::Clamp { min, max }, step }
+    }
+
+    pub const fn wrap(min: i32, max: i32, step: i32) -> Self {
+        Policy { range: Range::Wrap { min, max }, step }
+    }
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self::UNBOUNDED
+    }
+}
+
+/// Bring any value inside the range.
+pub fn fit(range: &Range, value: i32) -> i32 {
+    match *range {
+        Range::Unbounded => value,
+        Range::Clamp { min, max } => value.clamp(min, max.max(min)),
+        Range::Wrap { min, max } => wrap(min, max, value as i64),
+    }
+}
+
+/// Move `value` by `steps` (signed, e.g. the accelerated detents) of the policy step size.
+pub fn apply(policy: &Policy, value: i32, steps: i32) -> i32 {
+    let delta = steps as i64 * policy.step as i64;
+    match policy.range {
+        Range::Unbounded => (value as i64 + delta).clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+        Range::Clamp { min, max } => (value as i64 + delta).clamp(min as i64, max.max(min) as i64) as i32,
+        Range::Wrap { min, max } => wrap(min, max, value as i64 + delta),
+    }
+}
+
+fn wrap(min: i32, max: i32, value: i64) -> i32 {
+    let span = (max as i64 - min as i64 + 1).max(1);
+    ((value - min as i64).rem_euclid(span) + min as i64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_stops_at_the_bounds() {
+        let policy = Policy::clamp(1, 10, 1);
+        assert_eq!(apply(&policy, 5, 3), 8);
+        assert_eq!(apply(&policy, 5, -3), 2);
+        assert_eq!(apply(&policy, 5, 100), 10);
+        assert_eq!(apply(&policy, 5, -100), 1);
+        assert_eq!(fit(&policy.range, 42), 10);
+        assert_eq!(fit(&policy.range, -42), 1);
+    }
+
+    #[test]
+    fn wrap_goes_around_any_number_of_times() {
+        let policy = Policy::wrap(0, 9, 1);
+        assert_eq!(apply(&policy, 8, 3), 1);
+        assert_eq!(apply(&policy, 1, -3), 8);
+        assert_eq!(apply(&policy, 5, 25), 0);
+        assert_eq!(apply(&policy, 5, -26), 9);
+        assert_eq!(fit(&policy.range, 12), 2);
+        assert_eq!(fit(&policy.range, -1), 9);
+    }
+
+    #[test]
+    fn step_size_multiplies_the_steps() {
+        assert_eq!(apply(&Policy::clamp(0, 100, 5), 50, -3), 35);
+        assert_eq!(apply(&Policy::wrap(0, 59, 15), 45, 1), 0);
+        assert_eq!(apply(&Policy { range: Range::Unbounded, step: -2 }, 0, 3), -6);
+    }
+
+    #[test]
+    fn unbounded_saturates_at_the_i32_bounds() {
+        assert_eq!(apply(&Policy::UNBOUNDED, i32::MAX - 1, 5), i32::MAX);
+        assert_eq!(apply(&Policy::UNBOUNDED, i32::MIN + 1, -5), i32::MIN);
+        assert_eq!(apply(&Policy { range: Range::Unbounded, step: i32::MAX }, 0, i32::MIN), i32::MIN);
+        assert_eq!(fit(&Range::Unbounded, i32::MIN), i32::MIN);
+    }
+
+    #[test]
+    fn single_value_ranges_keep_it() {
+        for policy in [Policy::clamp(7, 7, 1), Policy::wrap(7, 7, 1)] {
+            assert_eq!(apply(&policy, 7, 1), 7);
+            assert_eq!(apply(&policy, 7, -1000), 7);
+            assert_eq!(fit(&policy.range, i32::MAX), 7);
+        }
+        // Inverted bounds collapse to the minimum, rather than panicking
+        assert_eq!(fit(&Range::Clamp { min: 5, max: 1 }, 3), 5);
+        assert_eq!(apply(&Policy::wrap(5, 1, 1), 3, 2), 5);
+    }
+
+    #[test]
+    fn ranges_spanning_all_of_i32_do_not_overflow() {
+        let clamp = Policy::clamp(i32::MIN, i32::MAX, i32::MAX);
+        assert_eq!(apply(&clamp, i32::MAX, i32::MAX), i32::MAX);
+        assert_eq!(apply(&clamp, i32::MIN, i32::MIN), i32::MIN);
+
+        let wrap = Policy::wrap(i32::MIN, i32::MAX, 1);
+        assert_eq!(apply(&wrap, i32::MAX, 1), i32::MIN);
+        assert_eq!(apply(&wrap, i32::MIN, -1), i32::MAX);
+        assert_eq!(apply(&Policy::wrap(i32::MIN, i32::MAX, i32::MIN), 0, i32::MIN), 0);
+        assert_eq!(fit(&wrap.range, i32::MIN), i32::MIN);
+    }
+}
@@ -0,0 +1,173 @@
+//! Events
+//! Fixed-capacity FIFO of events, shared by interrupts pushing into it and the main loop consuming it.
+//!
+//! The queue is changed in critical sections, entered through a `Lock` (interrupts disabled on the target), while
+//! its length is mirrored in an atomic: checking for events, or for dropped ones, enters none when there are none.
+
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use heapless::Deque;
+
+/// How a `Queue` enters its critical sections.
+///
+/// # Safety
+///
+/// Nothing else may touch the queue while `free` runs its closure: implementations must keep every other thread
+/// of execution (interrupts on the target) out, or only be used where there is none.
+pub unsafe trait Lock {
+    fn free<R>(f: impl FnOnce() -> R) -> R;
+}
+
+/// Interrupt-safe FIFO of at most `N` events, meant to be stored in a static.
+pub struct Queue<T, L: Lock, const N: usize> {
+    /// Only touched within the critical sections of `L`.
+    events: UnsafeCell<Deque<T, N>>,
+    /// Length of `events`, only changed in critical sections but read anywhere.
+    queued: AtomicUsize,
+    /// Events lost because the queue was full.
+    dropped: AtomicU32,
+    lock: PhantomData<L>,
+}
+
+// The events are only reached within the critical sections of `L`, which exclude each other
+unsafe impl<T: Send, L: Lock, const N: usize> Sync for Queue<T, L, N> {}
+
+impl<T, L: Lock, const N: usize> Queue<T, L, N> {
+    pub const fn new() -> Self {
+        Queue {
+            events: UnsafeCell::new(Deque::new()),
+            queued: AtomicUsize::new(0),
+            dropped: AtomicU32::new(0),
+            lock: PhantomData,
+        }
+    }
+
+    /// Append an event. If the queue is full the event is dropped and accounted for, keeping the older ones so
+    /// that the consumer still sees them in order.
+    pub fn push(&self, event: T) {
+        L::free(|| {
+            let events = unsafe { &mut *self.events.get() };
+            if events.push_back(event).is_err() {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+            self.queued.store(events.len(), Ordering::Release);
+        });
+    }
+
+    /// Take the oldest event, if any.
+    pub fn pop(&self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        L::free(|| {
+            let events = unsafe { &mut *self.events.get() };
+            let event = events.pop_front();
+            self.queued.store(events.len(), Ordering::Release);
+            event
+        })
+    }
+
+    /// Number of events waiting to be consumed.
+    pub fn len(&self) -> usize {
+        self.queued.load(Ordering::Acquire)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Discard all waiting events, e.g. clicks queued while a long operation was running.
+    pub fn clear(&self) {
+        L::free(|| {
+            unsafe { &mut *self.events.get() }.clear();
+            self.queued.store(0, Ordering::Release);
+        });
+    }
+
+    /// Events dropped so far because the queue was full.
+    pub fn dropped(&self) -> u32 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl<T, L: Lock, const N: usize> Default for Queue<T, L, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use std::thread_local;
+    use std::vec::Vec;
+
+    use super::*;
+
+    thread_local! {
+        static ENTERED: Cell<u32> = const { Cell::new(0) };
+    }
+
+    /// Critical sections of a single test thread, counted.
+    struct Counted;
+
+    unsafe impl Lock for Counted {
+        fn free<R>(f: impl FnOnce() -> R) -> R {
+            ENTERED.with(|entered| entered.set(entered.get() + 1));
+            f()
+        }
+    }
+
+    /// Critical sections entered by `f`.
+    fn entered_by(f: impl FnOnce()) -> u32 {
+        let before = ENTERED.with(Cell::get);
+        f();
+        ENTERED.with(Cell::get) - before
+    }
+
+    #[test]
+    fn events_come_out_in_order() {
+        let queue = Queue::<u32, Counted, 4>::new();
+        for event in 1..=3 {
+            queue.push(event);
+        }
+        assert_eq!(queue.len(), 3);
+        let events: Vec<_> = core::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(events, [1, 2, 3]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn new_events_are_dropped_once_full() {
+        let queue = Queue::<u32, Counted, 2>::new();
+        for event in 1..=5 {
+            queue.push(event);
+        }
+        assert_eq!(queue.dropped(), 3);
+        assert_eq!(queue.pop(), Some(1));
+        assert_eq!(queue.pop(), Some(2));
+
+        queue.push(6);
+        queue.clear();
+        assert_eq!(queue.pop(), None);
+        assert_eq!(queue.dropped(), 3);
+    }
+
+    #[test]
+    fn each_event_costs_one_critical_section_each_way() {
+        let queue = Queue::<u32, Counted, 4>::new();
+        assert_eq!(entered_by(|| queue.push(1)), 1);
+        assert_eq!(entered_by(|| assert_eq!(queue.pop(), Some(1))), 1);
+
+        // Polling an empty queue, as the main loop does on every wakeup, enters none
+        let polled = entered_by(|| {
+            for _ in 0..100 {
+                assert!(queue.is_empty());
+                assert_eq!(queue.pop(), None);
+                assert_eq!(queue.dropped(), 0);
+            }
+        });
+        assert_eq!(polled, 0);
+    }
+}
@@ -0,0 +1,225 @@
+//! Input
+//! High-level UI events, which the TimeSaver reacts to whatever the front panel producing them, and the mapping
+//! of the clicks of panel buttons and keys to them.
+
+use crate::button::{self, ButtonEvent, ButtonState, Timings};
+
+pub mod commands;
+pub mod keypad;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum UiEvent {
+    /// Move up by the given steps: one per key press, more for a fast (accelerated) turn of the knob.
+    Up(u32),
+    Down(u32),
+    /// Move up by the given steps while Select is held, e.g. turning the knob while pressing it.
+    HeldUp(u32),
+    HeldDown(u32),
+    Select,
+    Back,
+    LongSelect,
+}
+
+/// UI event of a click on the key mapped to `key`, if any: Select turns into LongSelect when long-pressed,
+/// Up and Down repeat while held, the others only react to short presses.
+pub fn from_click(key: UiEvent, click: ButtonEvent) -> Option<UiEvent> {
+    match (key, click) {
+        (UiEvent::Select, ButtonEvent::LongPress) => Some(UiEvent::LongSelect),
+        (UiEvent::Up(_) | UiEvent::Down(_), ButtonEvent::LongPress | ButtonEvent::Held) => Some(key),
+        (_, ButtonEvent::ShortPress) => Some(key),
+        _ => None,
+    }
+}
+
+/// Mapping of a rotary encoder with pushbutton: clockwise steps are Up, anticlockwise ones Down, and the
+/// pushbutton is Select. Turning it while pressed moves HeldUp or HeldDown instead, and the press then makes no
+/// click at all.
+#[derive(Default)]
+pub struct Knob {
+    pressed: bool,
+    /// Turned since the last press, whose click is to be dropped.
+    turned: bool,
+}
+
+impl Knob {
+    pub const fn new() -> Self {
+        Knob { pressed: false, turned: false }
+    }
+
+    /// UI event of a turn by the given steps: positive clockwise, negative anticlockwise.
+    pub fn turn(&mut self, steps: i32) -> Option<UiEvent> {
+        let count = steps.unsigned_abs();
+        self.turned |= self.pressed;
+        match (steps.signum(), self.pressed) {
+            (1, false) => Some(UiEvent::Up(count)),
+            (-1, false) => Some(UiEvent::Down(count)),
+            (1, true) => Some(UiEvent::HeldUp(count)),
+            (-1, true) => Some(UiEvent::HeldDown(count)),
+            _ => None,
+        }
+    }
+
+    /// UI event of a click of the pushbutton, if any.
+    pub fn click(&mut self, click: ButtonEvent) -> Option<UiEvent> {
+        match click {
+            ButtonEvent::Pressed => {
+                self.pressed = true;
+                self.turned = false;
+            }
+            // The short press of a click is only reported after the release
+            ButtonEvent::Released => self.pressed = false,
+            _ if self.turned => {}
+            _ => return from_click(UiEvent::Select, click),
+        }
+        None
+    }
+}
+
+/// Click detection of a pair of buttons moving Up and Down, repeating while held.
+pub struct ButtonPair {
+    up: ButtonState,
+    down: ButtonState,
+    timings: Timings,
+    /// Event of the Down button, when detected together with one of the Up button.
+    pending: Option<UiEvent>,
+}
+
+impl ButtonPair {
+    pub const fn new(timings: Timings) -> Self {
+        ButtonPair { up: ButtonState::new(), down: ButtonState::new(), timings, pending: None }
+    }
+
+    /// Feed samples of both buttons (`true` when pressed) taken at `now_ms`, returning the next UI event, if any.
+    /// While an event is pending, it is returned instead and the samples are skipped.
+    pub fn update(&mut self, up: bool, down: bool, now_ms: u32) -> Option<UiEvent> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+
+        // Both buttons are sampled every time, not to miss any edge
+        let (state, up_click) = button::update(self.up, &self.timings, up, now_ms);
+        self.up = state;
+        let (state, down_click) = button::update(self.down, &self.timings, down, now_ms);
+        self.down = state;
+
+        let up = up_click.and_then(|click| from_click(UiEvent::Up(1), click));
+        let down = down_click.and_then(|click| from_click(UiEvent::Down(1), click));
+        match up {
+            Some(event) => {
+                self.pending = down;
+                Some(event)
+            }
+            None => down,
+        }
+    }
+
+    /// Whether both buttons are released and done with their last click.
+    pub fn is_idle(&self) -> bool {
+        self.pending.is_none() && self.up.is_idle() && self.down.is_idle()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn clicks_map_to_their_key() {
+        for key in [UiEvent::Up(1), UiEvent::Down(1), UiEvent::Select, UiEvent::Back] {
+            assert_eq!(from_click(key, ButtonEvent::ShortPress), Some(key));
+            assert_eq!(from_click(key, ButtonEvent::Pressed), None);
+            assert_eq!(from_click(key, ButtonEvent::Released), None);
+            assert_eq!(from_click(key, ButtonEvent::DoubleClick), None);
+        }
+    }
+
+    #[test]
+    fn long_presses_depend_on_the_key() {
+        assert_eq!(from_click(UiEvent::Select, ButtonEvent::LongPress), Some(UiEvent::LongSelect));
+        assert_eq!(from_click(UiEvent::Select, ButtonEvent::Held), None);
+        assert_eq!(from_click(UiEvent::Up(1), ButtonEvent::LongPress), Some(UiEvent::Up(1)));
+        assert_eq!(from_click(UiEvent::Down(1), ButtonEvent::Held), Some(UiEvent::Down(1)));
+        assert_eq!(from_click(UiEvent::Back, ButtonEvent::LongPress), None);
+        assert_eq!(from_click(UiEvent::Back, ButtonEvent::Held), None);
+    }
+
+    #[test]
+    fn knob_turns_move_by_all_their_steps() {
+        let mut knob = Knob::new();
+        assert_eq!(knob.turn(3), Some(UiEvent::Up(3)));
+        assert_eq!(knob.turn(-1), Some(UiEvent::Down(1)));
+        assert_eq!(knob.turn(i32::MIN), Some(UiEvent::Down(1 << 31)));
+        assert_eq!(knob.turn(0), None);
+    }
+
+    #[test]
+    fn knob_clicks_are_select() {
+        let mut knob = Knob::new();
+        let clicks = [ButtonEvent::Pressed, ButtonEvent::Released, ButtonEvent::ShortPress];
+        let events: Vec<_> = clicks.into_iter().filter_map(|click| knob.click(click)).collect();
+        assert_eq!(events, [UiEvent::Select]);
+
+        let clicks = [ButtonEvent::Pressed, ButtonEvent::LongPress, ButtonEvent::Held, ButtonEvent::Released];
+        let events: Vec<_> = clicks.into_iter().filter_map(|click| knob.click(click)).collect();
+        assert_eq!(events, [UiEvent::LongSelect]);
+    }
+
+    #[test]
+    fn knob_turned_while_pressed_moves_held_and_makes_no_click() {
+        let mut knob = Knob::new();
+        assert_eq!(knob.click(ButtonEvent::Pressed), None);
+        assert_eq!(knob.turn(2), Some(UiEvent::HeldUp(2)));
+        assert_eq!(knob.turn(-1), Some(UiEvent::HeldDown(1)));
+        // However long it was pressed
+        assert_eq!(knob.click(ButtonEvent::LongPress), None);
+        assert_eq!(knob.click(ButtonEvent::Released), None);
+        assert_eq!(knob.turn(1), Some(UiEvent::Up(1)));
+        assert_eq!(knob.click(ButtonEvent::ShortPress), None);
+
+        // Back to plain clicks from the next press on
+        let clicks = [ButtonEvent::Pressed, ButtonEvent::Released, ButtonEvent::ShortPress];
+        let events: Vec<_> = clicks.into_iter().filter_map(|click| knob.click(click)).collect();
+        assert_eq!(events, [UiEvent::Select]);
+    }
+
+    /// Sample the pair every millisecond for `ms`, with the given levels, recording the events with their time.
+    fn sample(pair: &mut ButtonPair, now_ms: &mut u32, up: bool, down: bool, ms: u32) -> Vec<(u32, UiEvent)> {
+        let mut events = Vec::new();
+        for _ in 0..ms {
+            events.extend(pair.update(up, down, *now_ms).map(|event| (*now_ms, event)));
+            *now_ms += 1;
+        }
+        events
+    }
+
+    #[test]
+    fn button_pair_steps_and_repeats() {
+        let timings = Timings { double_click_ms: 0, ..Timings::default() };
+        let mut pair = ButtonPair::new(timings);
+        let mut now_ms = 0;
+
+        // A short press of Up is a single step, once released (and debounced)
+        assert!(sample(&mut pair, &mut now_ms, true, false, 100).is_empty());
+        assert_eq!(sample(&mut pair, &mut now_ms, false, false, 100), [(121, UiEvent::Up(1))]);
+        assert!(pair.is_idle());
+
+        // Holding Down repeats it, from the long press on
+        let held = sample(&mut pair, &mut now_ms, false, true, 1300);
+        let long_press_ms = now_ms - 1300 + timings.debounce_ms + timings.long_press_ms;
+        let expected: Vec<_> = (0..3).map(|n| (long_press_ms + n * timings.repeat_ms, UiEvent::Down(1))).collect();
+        assert_eq!(held, expected);
+        assert!(sample(&mut pair, &mut now_ms, false, false, 100).is_empty());
+        assert!(pair.is_idle());
+    }
+
+    #[test]
+    fn button_pair_delivers_simultaneous_clicks_in_turn() {
+        let mut pair = ButtonPair::new(Timings { double_click_ms: 0, ..Timings::default() });
+        let mut now_ms = 0;
+        sample(&mut pair, &mut now_ms, true, true, 100);
+        let events = sample(&mut pair, &mut now_ms, false, false, 100);
+        assert_eq!(events, [(121, UiEvent::Up(1)), (122, UiEvent::Down(1))]);
+    }
+}
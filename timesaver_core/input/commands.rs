@@ -0,0 +1,244 @@
+//! Text commands, one per line:
+//! `up`/`u`/`+`, `down`/`d`/`-`, `held up`/`h+`, `held down`/`h-`, `select`/`s`, `back`/`b` and `long`/`l` (case
+//! insensitive), the UI events,
+//! besides the device commands carried out by the firmware: `time YYYY-MM-DD HH:MM[:SS]` setting the wall clock,
+//! the drift calibration ones (`calibrate` against the LSE, or `ref <milliseconds>` for each reference timestamp
+//! and `calibrate ref` against them), `name <timer> <label>` renaming a countdown timer (counting from 1), and
+//! `encoder full|half|quarter`, `encoder invert on|off` and `encoder detents <detents>` configuring the knob.
+
+use heapless::Vec;
+
+use super::UiEvent;
+use crate::clock::DateTime;
+use crate::encoder_interface::decoder::{EncoderSetting, Resolution};
+use crate::timesaver::timers::Label;
+
+/// Longest command accepted; longer lines are discarded.
+const MAX_COMMAND_LEN: usize = 32;
+
+/// Device maintenance, which is up to the firmware rather than to the TimeSaver.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Command {
+    /// Set the wall clock to the given date and time.
+    SetClock(DateTime),
+    /// Measure the drift of the timers against the LSE, and correct it.
+    Calibrate,
+    /// Reference timestamp in milliseconds (e.g. Unix time of a host), to measure the drift of the timers against.
+    Reference(u64),
+    /// Correct the drift of the timers measured against the reference timestamps received so far.
+    CalibrateReference,
+    /// Rename the countdown timer with the given index.
+    Rename(usize, Label),
+    /// Change how the knob is decoded, and store it in the device configuration.
+    Encoder(EncoderSetting),
+}
+
+/// What a line of text asks for.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Line {
+    Ui(UiEvent),
+    Command(Command),
+}
+
+/// Parse a command line, ignoring surrounding whitespace.
+pub fn parse(line: &[u8]) -> Option<Line> {
+    let line = core::str::from_utf8(line).ok()?.trim();
+    let command = |names: &[&str]| names.iter().any(|name| line.eq_ignore_ascii_case(name));
+
+    if command(&["up", "u", "+"]) {
+        Some(Line::Ui(UiEvent::Up(1)))
+    } else if command(&["down", "d", "-"]) {
+        Some(Line::Ui(UiEvent::Down(1)))
+    } else if command(&["held up", "h+"]) {
+        Some(Line::Ui(UiEvent::HeldUp(1)))
+    } else if command(&["held down", "h-"]) {
+        Some(Line::Ui(UiEvent::HeldDown(1)))
+    } else if command(&["select", "s"]) {
+        Some(Line::Ui(UiEvent::Select))
+    } else if command(&["back", "b"]) {
+        Some(Line::Ui(UiEvent::Back))
+    } else if command(&["long", "l"]) {
+        Some(Line::Ui(UiEvent::LongSelect))
+    } else if command(&["calibrate"]) {
+        Some(Line::Command(Command::Calibrate))
+    } else if let Some((name, argument)) = line.split_once(' ') {
+        // Commands with an argument
+        let argument = argument.trim();
+        let command = if name.eq_ignore_ascii_case("time") {
+            DateTime::parse(argument).map(Command::SetClock)
+        } else if name.eq_ignore_ascii_case("ref") {
+            argument.parse().ok().map(Command::Reference)
+        } else if name.eq_ignore_ascii_case("calibrate") && argument.eq_ignore_ascii_case("ref") {
+            Some(Command::CalibrateReference)
+        } else if name.eq_ignore_ascii_case("name") {
+            let (timer, label) = argument.split_once(' ')?;
+            let index = timer.parse::<usize>().ok()?.checked_sub(1)?;
+            Label::new(label).map(|label| Command::Rename(index, label))
+        } else if name.eq_ignore_ascii_case("encoder") {
+            parse_encoder_setting(argument).map(Command::Encoder)
+        } else {
+            None
+        };
+        command.map(Line::Command)
+    } else {
+        None
+    }
+}
+
+/// Parse the argument of an `encoder` command.
+fn parse_encoder_setting(argument: &str) -> Option<EncoderSetting> {
+    let is = |name: &str| argument.eq_ignore_ascii_case(name);
+    if is("full") {
+        Some(EncoderSetting::Resolution(Resolution::Full))
+    } else if is("half") {
+        Some(EncoderSetting::Resolution(Resolution::Half))
+    } else if is("quarter") {
+        Some(EncoderSetting::Resolution(Resolution::Quarter))
+    } else {
+        let (name, value) = argument.split_once(' ')?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("invert") && value.eq_ignore_ascii_case("on") {
+            Some(EncoderSetting::Inverted(true))
+        } else if name.eq_ignore_ascii_case("invert") && value.eq_ignore_ascii_case("off") {
+            Some(EncoderSetting::Inverted(false))
+        } else if name.eq_ignore_ascii_case("detents") {
+            value.parse().ok().filter(|&detents| detents > 0).map(EncoderSetting::DetentsPerRevolution)
+        } else {
+            None
+        }
+    }
+}
+
+/// Line-based command parser, fed one byte at a time.
+pub struct CommandParser {
+    line: Vec<u8, MAX_COMMAND_LEN>,
+    /// The current line got too long, and will be discarded.
+    overflow: bool,
+}
+
+impl CommandParser {
+    pub const fn new() -> Self {
+        CommandParser { line: Vec::new(), overflow: false }
+    }
+
+    /// Feed a received byte, returning what the line it completes asks for, if anything.
+    pub fn feed(&mut self, byte: u8) -> Option<Line> {
+        match byte {
+            b'\n' | b'\r' => {
+                let line = if self.overflow { None } else { parse(&self.line) };
+                self.line.clear();
+                self.overflow = false;
+                line
+            }
+            _ => {
+                if self.line.push(byte).is_err() {
+                    self.overflow = true;
+                }
+                None
+            }
+        }
+    }
+
+    /// Whether a line is partly received, waiting for its end.
+    pub fn is_partial(&self) -> bool {
+        !self.line.is_empty() || self.overflow
+    }
+}
+
+impl Default for CommandParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn command(line: &str) -> Option<Command> {
+        match parse(line.as_bytes())? {
+            Line::Command(command) => Some(command),
+            Line::Ui(_) => None,
+        }
+    }
+
+    #[test]
+    fn ui_events_by_name_or_shortcut() {
+        assert_eq!(parse(b"up"), Some(Line::Ui(UiEvent::Up(1))));
+        assert_eq!(parse(b" + "), Some(Line::Ui(UiEvent::Up(1))));
+        assert_eq!(parse(b"D"), Some(Line::Ui(UiEvent::Down(1))));
+        assert_eq!(parse(b"held up"), Some(Line::Ui(UiEvent::HeldUp(1))));
+        assert_eq!(parse(b"h-"), Some(Line::Ui(UiEvent::HeldDown(1))));
+        assert_eq!(parse(b"Select"), Some(Line::Ui(UiEvent::Select)));
+        assert_eq!(parse(b"b"), Some(Line::Ui(UiEvent::Back)));
+        assert_eq!(parse(b"long"), Some(Line::Ui(UiEvent::LongSelect)));
+        assert_eq!(parse(b"upp"), None);
+        assert_eq!(parse(b""), None);
+    }
+
+    #[test]
+    fn device_commands() {
+        assert_eq!(command("time 2024-05-17 12:34"), Some(Command::SetClock(DateTime::new(2024, 5, 17, 12, 34, 0))));
+        assert_eq!(command("time 2024-02-30 12:34"), None);
+        assert_eq!(command("calibrate"), Some(Command::Calibrate));
+        assert_eq!(command("CALIBRATE  ref"), Some(Command::CalibrateReference));
+        assert_eq!(command("ref 1715949296000"), Some(Command::Reference(1_715_949_296_000)));
+        assert_eq!(command("ref soon"), None);
+        assert_eq!(command("name 2 Pasta"), Some(Command::Rename(1, Label::new("Pasta").unwrap())));
+        assert_eq!(command("name 0 Pasta"), None);
+        assert_eq!(command("name 2 Spaghetti alla carbonara"), None);
+    }
+
+    #[test]
+    fn encoder_commands() {
+        let setting = |line| match command(line) {
+            Some(Command::Encoder(setting)) => Some(setting),
+            _ => None,
+        };
+        assert_eq!(setting("encoder half"), Some(EncoderSetting::Resolution(Resolution::Half)));
+        assert_eq!(setting("Encoder QUARTER"), Some(EncoderSetting::Resolution(Resolution::Quarter)));
+        assert_eq!(setting("encoder full"), Some(EncoderSetting::Resolution(Resolution::Full)));
+        assert_eq!(setting("encoder invert on"), Some(EncoderSetting::Inverted(true)));
+        assert_eq!(setting("encoder invert  off"), Some(EncoderSetting::Inverted(false)));
+        assert_eq!(setting("encoder detents 24"), Some(EncoderSetting::DetentsPerRevolution(24)));
+        assert_eq!(setting("encoder detents 0"), None);
+        assert_eq!(setting("encoder detents 70000"), None);
+        assert_eq!(setting("encoder invert maybe"), None);
+        assert_eq!(setting("encoder double"), None);
+    }
+
+    #[test]
+    fn lines_are_fed_a_byte_at_a_time() {
+        let mut parser = CommandParser::new();
+        let lines: Vec<Line> = b"u\r\nname 1 Rice\nbogus\n".iter().filter_map(|&byte| parser.feed(byte)).collect();
+        assert_eq!(lines, [Line::Ui(UiEvent::Up(1)), Line::Command(Command::Rename(0, Label::new("Rice").unwrap()))]);
+
+        // Too long lines are discarded whole, and the next one is parsed anew
+        let lines: Vec<Line> = [[b'x'; MAX_COMMAND_LEN + 1].as_slice(), b" up\ndown\n"]
+            .concat()
+            .iter()
+            .filter_map(|&byte| parser.feed(byte))
+            .collect();
+        assert_eq!(lines, [Line::Ui(UiEvent::Down(1))]);
+    }
+
+    #[test]
+    fn partial_lines_are_told_apart() {
+        let mut parser = CommandParser::new();
+        assert!(!parser.is_partial());
+        parser.feed(b't');
+        assert!(parser.is_partial());
+        parser.feed(b'\n');
+        assert!(!parser.is_partial());
+
+        // Until the end of a too long line too
+        for _ in 0..=MAX_COMMAND_LEN {
+            parser.feed(b'x');
+        }
+        assert!(parser.is_partial());
+        parser.feed(b'\r');
+        assert!(!parser.is_partial());
+    }
+}
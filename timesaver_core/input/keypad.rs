@@ -0,0 +1,118 @@
+//! Keypad
+//! Click detection of a matrix keypad scanned one row at a time: every key is debounced and click-detected on
+//! its own, like a single button, and mapped to the UI event of its keymap.
+
+use heapless::Deque;
+
+use super::{from_click, UiEvent};
+use crate::button::{self, ButtonState, Timings};
+
+/// Events a single scan can detect before the next ones are delivered.
+const PENDING_EVENTS: usize = 8;
+
+/// Keys of a matrix keypad, with the UI event of each key (None for keys not used).
+pub struct KeyMatrix<const ROWS: usize, const COLS: usize> {
+    keymap: [[Option<UiEvent>; COLS]; ROWS],
+    timings: Timings,
+    keys: [[ButtonState; COLS]; ROWS],
+    pending: Deque<UiEvent, PENDING_EVENTS>,
+}
+
+impl<const ROWS: usize, const COLS: usize> KeyMatrix<ROWS, COLS> {
+    pub fn new(keymap: [[Option<UiEvent>; COLS]; ROWS], timings: Timings) -> Self {
+        KeyMatrix { keymap, timings, keys: [[ButtonState::new(); COLS]; ROWS], pending: Deque::new() }
+    }
+
+    /// Feed the levels of the keys of a row (`true` when pressed) read at `now_ms`, queueing the UI events of
+    /// the detected clicks.
+    pub fn update_row(&mut self, row: usize, pressed: [bool; COLS], now_ms: u32) {
+        for (col, pressed) in pressed.into_iter().enumerate() {
+            let (state, click) = button::update(self.keys[row][col], &self.timings, pressed, now_ms);
+            self.keys[row][col] = state;
+
+            let event = self.keymap[row][col].zip(click).and_then(|(key, click)| from_click(key, click));
+            if let Some(event) = event {
+                // Keys pressed together beyond the queue capacity are ignored
+                self.pending.push_back(event).ok();
+            }
+        }
+    }
+
+    /// Take the oldest UI event detected, if any.
+    pub fn pop(&mut self) -> Option<UiEvent> {
+        self.pending.pop_front()
+    }
+
+    /// Whether events detected by the last scans are waiting to be taken.
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    const KEYMAP: [[Option<UiEvent>; 3]; 2] = [
+        [Some(UiEvent::Up(1)), Some(UiEvent::Down(1)), None],
+        [Some(UiEvent::Select), Some(UiEvent::Back), Some(UiEvent::Up(1))],
+    ];
+
+    /// Scan the whole matrix every millisecond for `ms`, with the given keys pressed, collecting the events.
+    fn scan(matrix: &mut KeyMatrix<2, 3>, now_ms: &mut u32, pressed: &[(usize, usize)], ms: u32) -> Vec<UiEvent> {
+        let mut events = Vec::new();
+        for _ in 0..ms {
+            for row in 0..2 {
+                let levels = core::array::from_fn(|col| pressed.contains(&(row, col)));
+                matrix.update_row(row, levels, *now_ms);
+            }
+            events.extend(core::iter::from_fn(|| matrix.pop()));
+            *now_ms += 1;
+        }
+        events
+    }
+
+    fn keypad() -> KeyMatrix<2, 3> {
+        KeyMatrix::new(KEYMAP, Timings { double_click_ms: 0, ..Timings::default() })
+    }
+
+    #[test]
+    fn keys_map_to_their_events() {
+        let mut matrix = keypad();
+        let mut now_ms = 0;
+        for (key, event) in [((0, 0), UiEvent::Up(1)), ((0, 1), UiEvent::Down(1)), ((1, 1), UiEvent::Back)] {
+            assert!(scan(&mut matrix, &mut now_ms, &[key], 100).is_empty());
+            assert_eq!(scan(&mut matrix, &mut now_ms, &[], 100), [event]);
+        }
+    }
+
+    #[test]
+    fn long_presses_follow_the_key() {
+        let mut matrix = keypad();
+        let mut now_ms = 0;
+        assert_eq!(scan(&mut matrix, &mut now_ms, &[(1, 0)], 1000), [UiEvent::LongSelect]);
+        assert!(scan(&mut matrix, &mut now_ms, &[], 100).is_empty());
+        assert_eq!(scan(&mut matrix, &mut now_ms, &[(1, 2)], 1300), [UiEvent::Up(1); 3]);
+        assert!(scan(&mut matrix, &mut now_ms, &[(1, 1)], 1300).is_empty());
+        assert_eq!(scan(&mut matrix, &mut now_ms, &[], 100), []);
+    }
+
+    #[test]
+    fn unmapped_keys_are_ignored() {
+        let mut matrix = keypad();
+        let mut now_ms = 0;
+        scan(&mut matrix, &mut now_ms, &[(0, 2)], 1000);
+        assert!(scan(&mut matrix, &mut now_ms, &[], 100).is_empty());
+        assert!(!matrix.has_pending());
+    }
+
+    #[test]
+    fn keys_pressed_together_are_all_delivered() {
+        let mut matrix = keypad();
+        let mut now_ms = 0;
+        scan(&mut matrix, &mut now_ms, &[(0, 1), (1, 0)], 100);
+        assert_eq!(scan(&mut matrix, &mut now_ms, &[], 100), [UiEvent::Down(1), UiEvent::Select]);
+    }
+}
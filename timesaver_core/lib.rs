@@ -0,0 +1,23 @@
+//! # TimeSaver core
+//! Everything of the TimeSaver that does not touch the hardware: its state machine and screens, the software
+//! timers, the calendar, the decoding of the knob and of its pushbutton, and the layout of the stored configuration.
+//!
+//! The firmware feeds it with the time and the inputs read from the peripherals, and carries out the effects it
+//! asks for; being hardware-free, it builds and is tested on the host too.
+
+#![no_std]
+
+extern crate alloc;
+
+#[cfg(test)]
+extern crate std;
+
+pub mod button;
+pub mod clock;
+pub mod config;
+pub mod encoder_interface;
+pub mod events;
+pub mod input;
+pub mod millis;
+pub mod timesaver;
+pub mod ui;
@@ -0,0 +1,10 @@
+//! Millis
+//! Points in time and software timers on the millisecond counter, and the arithmetic behind it: the extension
+//! of the 32-bit hardware counter to 64 bits, and its drift correction.
+
+pub use instant::Instant;
+
+pub mod calibration;
+pub mod instant;
+pub mod overflow;
+pub mod scheduler;
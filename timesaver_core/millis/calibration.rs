@@ -0,0 +1,206 @@
+//! Drift correction of the millis counter, free of any hardware dependency.
+//! Drifts are in parts per million (ppm) of the counter against a reference: positive when it runs fast.
+
+/// Largest drift accepted, beyond the tolerance of the internal oscillator the timers may run from (±1%).
+pub const MAX_PPM: i32 = 50_000;
+
+const PPM_SCALE: u64 = 1_000_000;
+
+/// Minimum span of a reference stream to fit a drift to, as shorter ones are dominated by the latency of the
+/// link rather than by the drift.
+pub const MIN_REFERENCE_SPAN_US: u64 = 60_000_000;
+
+/// Drift of `measured_us` counted over `reference_us` of the reference, if within `MAX_PPM`.
+pub fn ppm(reference_us: u64, measured_us: u64) -> Option<i32> {
+    if reference_us == 0 {
+        return None;
+    }
+    let difference = measured_us as i64 - reference_us as i64;
+    let ppm = difference.checked_mul(PPM_SCALE as i64)? / reference_us as i64;
+    (ppm.abs() <= MAX_PPM as i64).then_some(ppm as i32)
+}
+
+/// Microseconds of the reference corresponding to `raw_us` counted with the given drift.
+pub fn correct(raw_us: u64, ppm: i32) -> u64 {
+    // raw * 1e6 / (1e6 + ppm), split so that no intermediate result overflows
+    let divisor = (PPM_SCALE as i64 + ppm.clamp(-MAX_PPM, MAX_PPM) as i64) as u64;
+    (raw_us / divisor).saturating_mul(PPM_SCALE).saturating_add((raw_us % divisor) * PPM_SCALE / divisor)
+}
+
+/// Microseconds counted with the given drift over `us` of the reference, the inverse of `correct`.
+pub fn uncorrect(us: u64, ppm: i32) -> u64 {
+    // us * (1e6 + ppm) / 1e6, rounded up so that correcting it back gives at least `us`
+    let multiplier = (PPM_SCALE as i64 + ppm.clamp(-MAX_PPM, MAX_PPM) as i64) as u64;
+    (us / PPM_SCALE).saturating_mul(multiplier).saturating_add(((us % PPM_SCALE) * multiplier).div_ceil(PPM_SCALE))
+}
+
+/// Drift fitted to a stream of reference timestamps (e.g. sent by a host over serial), each paired with the
+/// local microseconds it was received at. The link latency varies, so the drift is the slope of a least squares
+/// fit, getting more accurate the longer the stream.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReferenceFit {
+    /// First sample, which the others are relative to.
+    origin: Option<(u64, u64)>,
+    samples: u32,
+    mean_reference: f64,
+    mean_local: f64,
+    /// Sums of the products of the deviations from the means.
+    covariance: f64,
+    variance: f64,
+    span_us: u64,
+}
+
+impl ReferenceFit {
+    pub const fn new() -> Self {
+        ReferenceFit {
+            origin: None,
+            samples: 0,
+            mean_reference: 0.0,
+            mean_local: 0.0,
+            covariance: 0.0,
+            variance: 0.0,
+            span_us: 0,
+        }
+    }
+
+    /// Add a sample; ones older than the first are ignored.
+    pub fn add(&mut self, reference_us: u64, local_us: u64) {
+        let (origin_reference, origin_local) = *self.origin.get_or_insert((reference_us, local_us));
+        let (Some(reference), Some(local)) =
+            (reference_us.checked_sub(origin_reference), local_us.checked_sub(origin_local))
+        else {
+            return;
+        };
+        let (reference, local) = (reference as f64, local as f64);
+
+        // Running means and co-moments (Welford), which keep their precision over long streams
+        self.samples += 1;
+        let reference_deviation = reference - self.mean_reference;
+        self.mean_reference += reference_deviation / self.samples as f64;
+        self.mean_local += (local - self.mean_local) / self.samples as f64;
+        self.covariance += reference_deviation * (local - self.mean_local);
+        self.variance += reference_deviation * (reference - self.mean_reference);
+        self.span_us = self.span_us.max(reference_us - origin_reference);
+    }
+
+    /// Span of the reference timestamps received so far.
+    pub fn span_us(&self) -> u64 {
+        self.span_us
+    }
+
+    /// Fitted drift, once the stream spans at least `MIN_REFERENCE_SPAN_US`, if within `MAX_PPM`.
+    pub fn ppm(&self) -> Option<i32> {
+        if self.span_us < MIN_REFERENCE_SPAN_US || self.variance <= 0.0 {
+            return None;
+        }
+        let ppm = (self.covariance / self.variance - 1.0) * PPM_SCALE as f64;
+        // Round to the nearest, without the float functions of std
+        let ppm = (if ppm >= 0.0 { ppm + 0.5 } else { ppm - 0.5 }) as i64;
+        (ppm.abs() <= MAX_PPM as i64).then_some(ppm as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ppm_is_relative_to_the_reference() {
+        assert_eq!(ppm(1_000_000, 1_000_120), Some(120));
+        assert_eq!(ppm(1_000_000, 999_880), Some(-120));
+        assert_eq!(ppm(60_000_000, 60_000_000), Some(0));
+        // Truncated towards zero
+        assert_eq!(ppm(3_000_000, 3_000_001), Some(0));
+        assert_eq!(ppm(3_000_000, 2_999_996), Some(-1));
+    }
+
+    #[test]
+    fn ppm_is_only_given_within_the_max() {
+        assert_eq!(ppm(1_000_000, 1_050_000), Some(MAX_PPM));
+        assert_eq!(ppm(1_000_000, 950_000), Some(-MAX_PPM));
+        assert_eq!(ppm(1_000_000, 1_050_001), None);
+        assert_eq!(ppm(1_000_000, 949_999), None);
+        assert_eq!(ppm(0, 1), None);
+        // Differences too large to scale
+        assert_eq!(ppm(1, 1 << 62), None);
+    }
+
+    #[test]
+    fn correct_undoes_the_drift() {
+        assert_eq!(correct(1_000_120, 120), 1_000_000);
+        assert_eq!(correct(999_880, -120), 1_000_000);
+        assert_eq!(uncorrect(1_000_000, 120), 1_000_120);
+        assert_eq!(uncorrect(1_000_000, -120), 999_880);
+        assert_eq!(correct(123_456, 0), 123_456);
+        // Drifts beyond the max are clamped to it
+        assert_eq!(correct(1_050_000, 2 * MAX_PPM), 1_000_000);
+        assert_eq!(uncorrect(1_000_000, -2 * MAX_PPM), 950_000);
+    }
+
+    #[test]
+    fn correcting_back_gives_the_time_again() {
+        let times = [0, 1, 999_999, 1_000_000, 3_600_000_001, 1 << 53, u64::MAX / 2];
+        for ppm in [-MAX_PPM, -MAX_PPM + 1, -1, 0, 1, 123, MAX_PPM - 1, MAX_PPM] {
+            for us in times {
+                let counted = uncorrect(us, ppm);
+                let corrected = correct(counted, ppm);
+                assert!((us..=us + 1).contains(&corrected), "{} us at {} ppm came back as {}", us, ppm, corrected);
+                assert!(correct(counted.saturating_sub(1), ppm) <= us, "{} us at {} ppm", us, ppm);
+            }
+        }
+    }
+
+    #[test]
+    fn times_near_the_u64_limit_saturate() {
+        assert_eq!(uncorrect(u64::MAX, MAX_PPM), u64::MAX);
+        assert_eq!(uncorrect(u64::MAX - 1, 1), u64::MAX);
+        assert_eq!(correct(u64::MAX, -MAX_PPM), u64::MAX);
+        assert_eq!(correct(u64::MAX, 0), u64::MAX);
+        // Shrinking ones are exact
+        assert_eq!(uncorrect(u64::MAX, 0), u64::MAX);
+        assert_eq!(correct(u64::MAX, MAX_PPM), u64::MAX / 1_050_000 * 1_000_000 + u64::MAX % 1_050_000 * 20 / 21);
+    }
+
+    /// Reference timestamps every second for `seconds`, received by a counter drifting by `ppm`, with a link
+    /// latency of up to 40 ms (pseudo-random, but reproducible).
+    fn jittered_stream(fit: &mut ReferenceFit, seconds: u64, ppm: i32) {
+        let mut seed = 12345u32;
+        for second in 0..=seconds {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            let latency_us = (seed >> 8) as u64 % 40_000;
+            let reference_us = 1_700_000_000_000_000 + second * 1_000_000;
+            let local_us = 5_000_000 + uncorrect(second * 1_000_000 + latency_us, ppm);
+            fit.add(reference_us, local_us);
+        }
+    }
+
+    #[test]
+    fn reference_fit_finds_the_drift_through_the_jitter() {
+        for drift in [-MAX_PPM / 2, -250, 0, 37, 1_000] {
+            let mut fit = ReferenceFit::new();
+            jittered_stream(&mut fit, 600, drift);
+            assert_eq!(fit.span_us(), 600_000_000);
+            let fitted = fit.ppm().unwrap();
+            assert!((fitted - drift).abs() <= 5, "{} ppm fitted to {} ppm", fitted, drift);
+        }
+    }
+
+    #[test]
+    fn reference_fit_needs_a_long_enough_stream() {
+        let mut fit = ReferenceFit::new();
+        assert_eq!(fit.ppm(), None);
+        jittered_stream(&mut fit, MIN_REFERENCE_SPAN_US / 1_000_000 - 1, 100);
+        assert_eq!(fit.ppm(), None);
+
+        // Samples older than the first one are ignored
+        let mut fit = ReferenceFit::new();
+        fit.add(10_000_000, 10_000_000);
+        fit.add(5_000_000, 5_000_000);
+        fit.add(10_000_000 + MIN_REFERENCE_SPAN_US, 10_000_000 + MIN_REFERENCE_SPAN_US + 6_000);
+        assert_eq!(fit.ppm(), Some(100));
+
+        // Drifts beyond the max are not trusted
+        fit.add(10_000_000 + 2 * MIN_REFERENCE_SPAN_US, 10_000_000 + 3 * MIN_REFERENCE_SPAN_US);
+        assert_eq!(fit.ppm(), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! Points in time on the 64-bit millis counter, free of any hardware dependency.
+//! The counter never wraps in practice (it would take 584 million years), and the arithmetic on it is checked
+//! or saturating, so that no overflow can panic. Lengths of time are `core::time::Duration`s.
+
+use core::time::Duration;
+
+/// Milliseconds from start time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant(u64);
+
+/// Milliseconds in a duration, if they fit in 64 bits.
+pub fn duration_millis(duration: Duration) -> Option<u64> {
+    duration.as_secs().checked_mul(1000)?.checked_add(duration.subsec_millis() as u64)
+}
+
+impl Instant {
+    pub const ZERO: Instant = Instant(0);
+    pub const MAX: Instant = Instant(u64::MAX);
+
+    pub const fn from_millis(ms: u64) -> Self {
+        Instant(ms)
+    }
+
+    pub const fn as_millis(&self) -> u64 {
+        self.0
+    }
+
+    /// Time elapsed from `earlier` to this instant, or None if `earlier` is later.
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.0.checked_sub(earlier.0).map(Duration::from_millis)
+    }
+
+    /// Time elapsed from `earlier` to this instant, or zero if `earlier` is later.
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.checked_duration_since(earlier).unwrap_or(Duration::ZERO)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        duration_millis(duration).and_then(|ms| self.0.checked_add(ms)).map(Instant)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        duration_millis(duration).and_then(|ms| self.0.checked_sub(ms)).map(Instant)
+    }
+
+    /// Add a duration, stopping at `Instant::MAX`.
+    pub fn saturating_add(&self, duration: Duration) -> Instant {
+        self.checked_add(duration).unwrap_or(Instant::MAX)
+    }
+
+    /// Subtract a duration, stopping at `Instant::ZERO`.
+    pub fn saturating_sub(&self, duration: Duration) -> Instant {
+        self.checked_sub(duration).unwrap_or(Instant::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_MS: Duration = Duration::from_millis(u64::MAX);
+
+    #[test]
+    fn durations_in_millis() {
+        assert_eq!(duration_millis(Duration::from_micros(1999)), Some(1), "sub-milliseconds are dropped");
+        assert_eq!(duration_millis(MAX_MS), Some(u64::MAX));
+        assert_eq!(duration_millis(MAX_MS + Duration::from_millis(1)), None);
+        assert_eq!(duration_millis(Duration::MAX), None);
+    }
+
+    #[test]
+    fn adding_near_the_end_of_time() {
+        let almost = Instant::from_millis(u64::MAX - 1);
+        assert_eq!(almost.checked_add(Duration::from_millis(1)), Some(Instant::MAX));
+        assert_eq!(almost.checked_add(Duration::from_millis(2)), None);
+        assert_eq!(almost.saturating_add(Duration::from_millis(2)), Instant::MAX);
+        assert_eq!(Instant::ZERO.checked_add(MAX_MS), Some(Instant::MAX));
+        assert_eq!(Instant::ZERO.checked_add(Duration::MAX), None);
+        assert_eq!(Instant::ZERO.saturating_add(Duration::MAX), Instant::MAX);
+    }
+
+    #[test]
+    fn subtracting_near_the_start_of_time() {
+        let early = Instant::from_millis(1);
+        assert_eq!(early.checked_sub(Duration::from_millis(1)), Some(Instant::ZERO));
+        assert_eq!(early.checked_sub(Duration::from_millis(2)), None);
+        assert_eq!(early.saturating_sub(Duration::from_millis(2)), Instant::ZERO);
+        assert_eq!(Instant::MAX.checked_sub(MAX_MS), Some(Instant::ZERO));
+        assert_eq!(Instant::MAX.saturating_sub(Duration::MAX), Instant::ZERO);
+    }
+
+    #[test]
+    fn elapsed_time_across_the_whole_range() {
+        assert_eq!(Instant::MAX.checked_duration_since(Instant::ZERO), Some(MAX_MS));
+        assert_eq!(Instant::ZERO.checked_duration_since(Instant::MAX), None);
+        assert_eq!(Instant::ZERO.saturating_duration_since(Instant::MAX), Duration::ZERO);
+        assert_eq!(Instant::MAX.saturating_duration_since(Instant::MAX), Duration::ZERO);
+    }
+
+    #[test]
+    fn past_the_32_bit_wrap() {
+        // Instants keep counting where the 32-bit millis wrap (~49.7 days)
+        let before = Instant::from_millis(u32::MAX as u64);
+        let after = before.saturating_add(Duration::from_millis(2));
+        assert_eq!(after.as_millis(), (1 << 32) + 1);
+        assert!(after > before);
+        assert_eq!(after.saturating_duration_since(before), Duration::from_millis(2));
+        assert_eq!((after.as_millis() as u32).wrapping_sub(before.as_millis() as u32), 2);
+    }
+}
@@ -0,0 +1,170 @@
+//! Extension of a free-running 32-bit counter to 64 bits, counting its overflows in an interrupt, free of any
+//! hardware dependency: the registers are read through `OverflowCounter`.
+//!
+//! Reading the overflows and the counter is not atomic, so the counter may wrap in between, and its overflow
+//! may not be counted yet (e.g. when read with interrupts disabled): `read` sorts both cases out.
+
+/// A free-running 32-bit counter, whose overflows are counted by an interrupt.
+pub trait OverflowCounter {
+    /// Overflows counted so far by the interrupt, i.e. the high word.
+    fn overflows(&mut self) -> u32;
+
+    /// Current value of the counter, i.e. the low word.
+    fn counter(&mut self) -> u32;
+
+    /// Whether the counter overflowed without its interrupt counting it yet.
+    fn overflow_pending(&mut self) -> bool;
+}
+
+/// Current value of the counter, extended to 64 bits by its overflows.
+pub fn read(counter: &mut impl OverflowCounter) -> u64 {
+    loop {
+        let overflows = counter.overflows();
+        let mut low = counter.counter();
+        let mut high = overflows;
+
+        // The counter may have overflowed without its interrupt counting it yet: read it again, as it may have
+        // overflowed right after the first read
+        if counter.overflow_pending() {
+            low = counter.counter();
+            high = high.wrapping_add(1);
+        }
+
+        // Retry if the interrupt counted an overflow meanwhile
+        if counter.overflows() == overflows {
+            return ((high as u64) << 32) | low as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::vec::Vec;
+
+    use super::*;
+
+    /// Registers giving back recorded readings, in order.
+    struct Recorded {
+        overflows: VecDeque<u32>,
+        counter: VecDeque<u32>,
+        pending: VecDeque<bool>,
+    }
+
+    impl Recorded {
+        fn new(overflows: &[u32], counter: &[u32], pending: &[bool]) -> Self {
+            Recorded {
+                overflows: overflows.iter().copied().collect(),
+                counter: counter.iter().copied().collect(),
+                pending: pending.iter().copied().collect(),
+            }
+        }
+    }
+
+    impl OverflowCounter for Recorded {
+        fn overflows(&mut self) -> u32 {
+            self.overflows.pop_front().expect("overflows read too many times")
+        }
+
+        fn counter(&mut self) -> u32 {
+            self.counter.pop_front().expect("counter read too many times")
+        }
+
+        fn overflow_pending(&mut self) -> bool {
+            self.pending.pop_front().expect("overflow flag read too many times")
+        }
+    }
+
+    /// Counter advancing by a tick on every reading, with its overflow interrupt enabled or not.
+    struct Simulated {
+        ticks: u64,
+        counted: u32,
+        interrupts: bool,
+    }
+
+    impl Simulated {
+        fn serve_interrupt(&mut self) {
+            if self.interrupts {
+                self.counted = (self.ticks >> 32) as u32;
+            }
+        }
+    }
+
+    impl OverflowCounter for Simulated {
+        fn overflows(&mut self) -> u32 {
+            self.serve_interrupt();
+            self.counted
+        }
+
+        fn counter(&mut self) -> u32 {
+            let counter = self.ticks as u32;
+            self.ticks += 1;
+            self.serve_interrupt();
+            counter
+        }
+
+        fn overflow_pending(&mut self) -> bool {
+            (self.ticks >> 32) as u32 != self.counted
+        }
+    }
+
+    #[test]
+    fn just_before_the_wrap() {
+        let mut counter = Recorded::new(&[0, 0], &[0xffff_ffff], &[false]);
+        assert_eq!(read(&mut counter), 0xffff_ffff);
+    }
+
+    #[test]
+    fn at_the_wrap() {
+        let mut counter = Recorded::new(&[1, 1], &[0], &[false]);
+        assert_eq!(read(&mut counter), 1 << 32);
+    }
+
+    #[test]
+    fn after_the_wrap() {
+        let mut counter = Recorded::new(&[1, 1], &[7], &[false]);
+        assert_eq!(read(&mut counter), (1 << 32) | 7);
+    }
+
+    #[test]
+    fn overflow_not_counted_yet() {
+        // e.g. with interrupts disabled: the counter wrapped before being read
+        let mut counter = Recorded::new(&[0, 0], &[3, 4], &[true]);
+        assert_eq!(read(&mut counter), (1 << 32) | 4);
+    }
+
+    #[test]
+    fn pending_overflow_racing_the_counter_read() {
+        // The counter is read right before wrapping, and the flag right after: taking the first reading with
+        // the high word incremented would jump ahead by 2^32
+        let mut counter = Recorded::new(&[0, 0], &[0xffff_ffff, 1], &[true]);
+        assert_eq!(read(&mut counter), (1 << 32) | 1);
+    }
+
+    #[test]
+    fn overflow_counted_while_reading() {
+        // The interrupt counted the overflow between the two readings of the high word
+        let mut counter = Recorded::new(&[0, 1, 1, 1], &[0xffff_ffff, 2], &[false, false]);
+        assert_eq!(read(&mut counter), (1 << 32) | 2);
+    }
+
+    #[test]
+    fn high_word_is_the_whole_overflow_count() {
+        let mut counter = Recorded::new(&[u32::MAX, u32::MAX], &[5], &[false]);
+        assert_eq!(read(&mut counter), (u32::MAX as u64) << 32 | 5);
+    }
+
+    #[test]
+    fn never_goes_back_across_the_wrap() {
+        for interrupts in [true, false] {
+            let start = (1 << 32) - 6;
+            let mut counter = Simulated { ticks: start, counted: 0, interrupts };
+            let readings: Vec<u64> = (0..12).map(|_| read(&mut counter)).collect();
+
+            assert!(readings.windows(2).all(|pair| pair[0] < pair[1]), "{:x?}", readings);
+            assert!(readings[0] >= start);
+            assert!(*readings.last().unwrap() < counter.ticks, "{:x?}", readings);
+            assert!(readings.iter().any(|&reading| reading > 1 << 32));
+        }
+    }
+}
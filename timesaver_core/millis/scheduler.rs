@@ -0,0 +1,267 @@
+//! Software timers keyed by deadline, free of any hardware dependency: the current instant is passed in, so
+//! that timers fire however late the caller gets to check them, and never more than once per deadline.
+
+use core::time::Duration;
+
+use super::instant::{duration_millis, Instant};
+
+/// What a periodic timer does when the scheduler is polled after more than a period has gone by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CatchUp {
+    /// Fire once for each missed period, e.g. to count every minute of a countdown.
+    Burst,
+    /// Fire once, and skip to the next period still in the future, e.g. for animations.
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Timer<T> {
+    tag: T,
+    deadline: Instant,
+    /// Period and catch-up policy of periodic timers, None for one-shot ones.
+    period: Option<(u64, CatchUp)>,
+}
+
+/// Up to N timers, each identified by a tag chosen by the caller (e.g. an enum of the things to do).
+pub struct Scheduler<T, const N: usize> {
+    timers: [Option<Timer<T>>; N],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// All the N timers are already in use.
+    Full,
+    /// The deadline is beyond the range of `Instant`.
+    Overflow,
+}
+
+impl<T: Copy + PartialEq, const N: usize> Scheduler<T, N> {
+    pub const fn new() -> Self {
+        Scheduler { timers: [None; N] }
+    }
+
+    fn add(&mut self, timer: Timer<T>) -> Result<(), Error> {
+        let slot = self.timers.iter_mut().find(|slot| slot.is_none()).ok_or(Error::Full)?;
+        *slot = Some(timer);
+        Ok(())
+    }
+
+    /// Fire `tag` once, at the given deadline.
+    pub fn schedule_at(&mut self, tag: T, deadline: Instant) -> Result<(), Error> {
+        self.add(Timer { tag, deadline, period: None })
+    }
+
+    /// Fire `tag` once, `delay` after `now`.
+    pub fn schedule_after(&mut self, tag: T, now: Instant, delay: Duration) -> Result<(), Error> {
+        let deadline = now.checked_add(delay).ok_or(Error::Overflow)?;
+        self.schedule_at(tag, deadline)
+    }
+
+    /// Fire `tag` every `period` (at least 1 ms) from `now`, the first time after a period.
+    pub fn schedule_every(&mut self, tag: T, now: Instant, period: Duration, catch_up: CatchUp) -> Result<(), Error> {
+        let period_ms = duration_millis(period).ok_or(Error::Overflow)?.max(1);
+        let deadline = now.checked_add(Duration::from_millis(period_ms)).ok_or(Error::Overflow)?;
+        self.add(Timer { tag, deadline, period: Some((period_ms, catch_up)) })
+    }
+
+    /// Cancel all the timers with the given tag.
+    pub fn cancel(&mut self, tag: T) {
+        self.cancel_where(|timer_tag| timer_tag == tag);
+    }
+
+    /// Cancel all the timers whose tag matches, e.g. all the variants of a tag enum but some.
+    pub fn cancel_where(&mut self, mut matches: impl FnMut(T) -> bool) {
+        for slot in self.timers.iter_mut() {
+            if matches!(slot, Some(timer) if matches(timer.tag)) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Earliest deadline among all timers, to decide how long to sleep.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.timers.iter().flatten().map(|timer| timer.deadline).min()
+    }
+
+    /// Take the tag of the timer with the earliest deadline not later than `now`, if any.
+    /// Call it until it returns None, to get all the expired timers in deadline order.
+    pub fn poll(&mut self, now: Instant) -> Option<T> {
+        let slot = self
+            .timers
+            .iter_mut()
+            .filter(|slot| matches!(slot, Some(timer) if timer.deadline <= now))
+            .min_by_key(|slot| slot.map(|timer| timer.deadline))?;
+        let timer = slot.take()?;
+
+        if let Some((period_ms, catch_up)) = timer.period {
+            let next_deadline = match catch_up {
+                CatchUp::Burst => timer.deadline.as_millis().checked_add(period_ms),
+                CatchUp::Skip => {
+                    let missed_periods = (now.as_millis() - timer.deadline.as_millis()) / period_ms;
+                    let periods_ms = (missed_periods + 1).checked_mul(period_ms);
+                    periods_ms.and_then(|ms| timer.deadline.as_millis().checked_add(ms))
+                }
+            };
+            // Periodic timers stop at the end of time, rather than firing there forever
+            if let Some(next_deadline) = next_deadline {
+                *slot = Some(Timer { deadline: Instant::from_millis(next_deadline), ..timer });
+            }
+        }
+        Some(timer.tag)
+    }
+}
+
+impl<T: Copy + PartialEq, const N: usize> Default for Scheduler<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    enum Tag {
+        A,
+        B,
+        C,
+        Numbered(u8),
+    }
+
+    fn at(ms: u64) -> Instant {
+        Instant::from_millis(ms)
+    }
+
+    fn poll_all<const N: usize>(scheduler: &mut Scheduler<Tag, N>, now_ms: u64) -> Vec<Tag> {
+        core::iter::from_fn(|| scheduler.poll(at(now_ms))).collect()
+    }
+
+    #[test]
+    fn one_shots_fire_once_in_deadline_order() {
+        let mut scheduler = Scheduler::<Tag, 4>::new();
+        scheduler.schedule_at(Tag::A, at(300)).unwrap();
+        scheduler.schedule_after(Tag::B, at(0), Duration::from_millis(100)).unwrap();
+        scheduler.schedule_at(Tag::C, at(200)).unwrap();
+        assert_eq!(scheduler.next_deadline(), Some(at(100)));
+
+        assert_eq!(poll_all(&mut scheduler, 99), []);
+        assert_eq!(poll_all(&mut scheduler, 250), [Tag::B, Tag::C]);
+        assert_eq!(poll_all(&mut scheduler, 1000), [Tag::A]);
+        assert_eq!(poll_all(&mut scheduler, 2000), []);
+        assert_eq!(scheduler.next_deadline(), None);
+    }
+
+    #[test]
+    fn deadlines_sharing_a_tick_all_fire() {
+        let mut scheduler = Scheduler::<Tag, 4>::new();
+        scheduler.schedule_at(Tag::C, at(100)).unwrap();
+        scheduler.schedule_at(Tag::A, at(100)).unwrap();
+        scheduler.schedule_every(Tag::B, at(50), Duration::from_millis(50), CatchUp::Skip).unwrap();
+
+        let fired = poll_all(&mut scheduler, 100);
+        assert_eq!(fired.len(), 3);
+        for tag in [Tag::A, Tag::B, Tag::C] {
+            assert!(fired.contains(&tag), "{:?} in {:?}", tag, fired);
+        }
+        assert_eq!(scheduler.next_deadline(), Some(at(150)));
+    }
+
+    #[test]
+    fn burst_catches_up_every_missed_period() {
+        let mut scheduler = Scheduler::<Tag, 4>::new();
+        scheduler.schedule_every(Tag::A, at(0), Duration::from_millis(100), CatchUp::Burst).unwrap();
+
+        // Stalled for 3 and a half periods
+        assert_eq!(poll_all(&mut scheduler, 350), [Tag::A, Tag::A, Tag::A]);
+        assert_eq!(scheduler.next_deadline(), Some(at(400)));
+        assert_eq!(poll_all(&mut scheduler, 400), [Tag::A]);
+    }
+
+    #[test]
+    fn skip_fires_once_after_a_stall() {
+        let mut scheduler = Scheduler::<Tag, 4>::new();
+        scheduler.schedule_every(Tag::A, at(0), Duration::from_millis(100), CatchUp::Skip).unwrap();
+
+        assert_eq!(poll_all(&mut scheduler, 350), [Tag::A]);
+        // Still in step with the first deadline
+        assert_eq!(scheduler.next_deadline(), Some(at(400)));
+        assert_eq!(poll_all(&mut scheduler, 400), [Tag::A]);
+        assert_eq!(poll_all(&mut scheduler, 10_000), [Tag::A]);
+        assert_eq!(scheduler.next_deadline(), Some(at(10_100)));
+    }
+
+    #[test]
+    fn periods_shorter_than_a_millisecond_are_one() {
+        let mut scheduler = Scheduler::<Tag, 1>::new();
+        scheduler.schedule_every(Tag::A, at(0), Duration::from_micros(10), CatchUp::Burst).unwrap();
+        assert_eq!(poll_all(&mut scheduler, 3).len(), 3);
+    }
+
+    #[test]
+    fn cancel_removes_every_timer_of_a_tag() {
+        let mut scheduler = Scheduler::<Tag, 4>::new();
+        scheduler.schedule_at(Tag::A, at(100)).unwrap();
+        scheduler.schedule_every(Tag::A, at(0), Duration::from_millis(10), CatchUp::Skip).unwrap();
+        scheduler.schedule_at(Tag::B, at(200)).unwrap();
+
+        scheduler.cancel(Tag::A);
+        assert_eq!(poll_all(&mut scheduler, 1000), [Tag::B]);
+
+        // Nothing to cancel
+        scheduler.cancel(Tag::C);
+    }
+
+    #[test]
+    fn cancel_where_matches_tags() {
+        let mut scheduler = Scheduler::<Tag, 4>::new();
+        for number in 0..3 {
+            scheduler.schedule_at(Tag::Numbered(number), at(100 + number as u64)).unwrap();
+        }
+        scheduler.schedule_at(Tag::A, at(50)).unwrap();
+
+        scheduler.cancel_where(|tag| matches!(tag, Tag::Numbered(number) if number != 1));
+        assert_eq!(poll_all(&mut scheduler, 1000), [Tag::A, Tag::Numbered(1)]);
+    }
+
+    #[test]
+    fn full_until_a_timer_fires_or_is_cancelled() {
+        let mut scheduler = Scheduler::<Tag, 2>::new();
+        scheduler.schedule_at(Tag::A, at(100)).unwrap();
+        scheduler.schedule_every(Tag::B, at(0), Duration::from_millis(100), CatchUp::Skip).unwrap();
+        assert!(matches!(scheduler.schedule_at(Tag::C, at(100)), Err(Error::Full)));
+        assert!(matches!(
+            scheduler.schedule_every(Tag::C, at(0), Duration::from_millis(1), CatchUp::Skip),
+            Err(Error::Full)
+        ));
+
+        // Periodic timers keep their slot when firing, one-shots give it back
+        assert_eq!(poll_all(&mut scheduler, 100), [Tag::A, Tag::B]);
+        scheduler.schedule_at(Tag::C, at(300)).unwrap();
+        assert!(matches!(scheduler.schedule_at(Tag::A, at(400)), Err(Error::Full)));
+        scheduler.cancel(Tag::B);
+        scheduler.schedule_at(Tag::A, at(400)).unwrap();
+    }
+
+    #[test]
+    fn deadlines_beyond_the_end_of_time_overflow() {
+        let mut scheduler = Scheduler::<Tag, 2>::new();
+        let late = Instant::MAX.saturating_sub(Duration::from_millis(10));
+        assert!(matches!(scheduler.schedule_after(Tag::A, late, Duration::from_millis(11)), Err(Error::Overflow)));
+        assert!(matches!(
+            scheduler.schedule_every(Tag::A, late, Duration::from_millis(11), CatchUp::Skip),
+            Err(Error::Overflow)
+        ));
+        assert_eq!(scheduler.next_deadline(), None);
+
+        // Periodic timers stop at the end of time
+        for catch_up in [CatchUp::Burst, CatchUp::Skip] {
+            scheduler.schedule_every(Tag::B, late, Duration::from_millis(10), catch_up).unwrap();
+            assert_eq!(scheduler.next_deadline(), Some(Instant::MAX));
+            assert_eq!(poll_all(&mut scheduler, u64::MAX), [Tag::B]);
+            assert_eq!(scheduler.next_deadline(), None);
+        }
+    }
+}
@@ -0,0 +1,818 @@
+//! TimeSaver
+//! State machine of the TimeSaver, free of any hardware dependency: it is driven by events and by the current
+//! time passed in, and it returns the effects to perform on the hardware, while what to show on screen is
+//! rendered from its state (see `ui::render`).
+//!
+//! Several countdown timers can run at once (see `timers`): the one on screen is the most urgent, unless
+//! another one is scrolled to, and each rings on its own. A Pomodoro (see `pomodoro`) can go on meanwhile.
+
+use core::mem;
+use core::time::Duration;
+
+use heapless::Vec;
+
+use crate::clock::calendar::{DateTime, Field};
+use crate::clock::YEARS;
+use crate::encoder_interface::range::{self, Policy};
+use crate::input::UiEvent;
+use crate::millis::scheduler::{self, CatchUp, Scheduler};
+use crate::millis::Instant;
+use pomodoro::{Pomodoro, Session};
+use timers::{Label, Status, Timer};
+
+pub mod pomodoro;
+pub mod timers;
+#[cfg(test)]
+mod tests;
+
+/// Countdown timers that can run at once.
+pub const TIMERS: usize = 4;
+
+/// Time the screen stays on a timer scrolled to, before going back to the most urgent one.
+const REFOCUS_DELAY: Duration = Duration::from_secs(5);
+
+/// Length of the alert between the phases of a Pomodoro.
+const POMODORO_ALERT: Duration = Duration::from_secs(3);
+
+const DEFAULT_MINUTES_TO_GO: u32 = 20;
+
+/// Minutes that can be selected, at least 1 and as many as printable in 3 digits.
+const MAX_MINUTES: u32 = 999;
+const MINUTES_POLICY: Policy = Policy::clamp(1, MAX_MINUTES as i32, 1);
+
+/// Wall clock time to start setting the clock from, when it has never been set.
+const DEFAULT_DATE_TIME: DateTime = DateTime::new(YEARS.0, 1, 1, 0, 0, 0);
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum TimeSaverState {
+    Splash,
+    ClockSetting,
+    Setting,
+    Count,
+    /// Adding or removing minutes to the timer on screen, while it keeps running.
+    Adjusting,
+    Paused,
+    AlarmClockSetting,
+    AlarmClock,
+    Alarm,
+    PomodoroSetting,
+    Pomodoro,
+    /// Short alert at the start of each phase of the Pomodoro.
+    PomodoroAlert,
+}
+
+/// Timed actions of the states.
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Tick {
+    Second,
+    /// Deadline of the timer with the given index, scheduled whatever the state.
+    End(usize),
+    Animation,
+    Blink,
+    Clock,
+    Refocus,
+    /// Deadline of the current phase of the Pomodoro, scheduled whatever the state too.
+    PhaseEnd,
+    AlertEnd,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Event {
+    /// UI event, with the time its input was detected at.
+    Ui(UiEvent, Instant),
+    /// The alarm set through `Effect::SetAlarm` rang.
+    AlarmRang,
+    /// Nothing happened, but time went by: timed actions may be due.
+    Tick,
+}
+
+/// Current time, as seen by the TimeSaver.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Now {
+    pub instant: Instant,
+    /// Wall clock time, if the clock is set.
+    pub wall_clock: Option<DateTime>,
+}
+
+/// Actions on the hardware, besides the screen.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Effect {
+    /// The screen content changed, and must be rendered again.
+    Redraw,
+    /// Switch the activity LED, following the animation.
+    Led(bool),
+    SetClock(DateTime),
+    /// Ring at the next occurrence of the time of day (hours and minutes), reported as `Event::AlarmRang`.
+    SetAlarm(DateTime),
+    CancelAlarm,
+    /// A timed action could not be scheduled, so the TimeSaver will miss it.
+    Fault(scheduler::Error),
+}
+
+/// Time left after adding the given minutes (removing them if negative), down to a minute and up to the longest
+/// countdown that can be selected, without cutting the time already left.
+fn add_minutes(left: Duration, minutes: i32) -> Duration {
+    let minute = Duration::from_secs(60);
+    let added = match u32::try_from(minutes) {
+        Ok(minutes) => left.saturating_add(minute * minutes),
+        Err(_) => left.saturating_sub(minute * minutes.unsigned_abs()),
+    };
+    added.clamp(left.min(minute), left.max(minute * MAX_MINUTES))
+}
+
+/// Effects of a single event, each at most once (the latest of each kind wins), with room to spare.
+pub type Effects = Vec<Effect, 8>;
+
+fn emit(effects: &mut Effects, effect: Effect) {
+    match effects.iter_mut().find(|emitted| mem::discriminant(*emitted) == mem::discriminant(&effect)) {
+        Some(emitted) => *emitted = effect,
+        None => effects.push(effect).unwrap_or(()), // there is room for one effect of each kind
+    }
+}
+
+/// Whole seconds of a duration, rounded up (so 0 only once over).
+fn whole_seconds(left: Duration) -> u32 {
+    (left.as_secs() + (left.subsec_nanos() > 0) as u64) as u32
+}
+
+fn seconds_until(now: Instant, end: Instant) -> u32 {
+    whole_seconds(end.saturating_duration_since(now))
+}
+
+const fn default_timers() -> [Timer; TIMERS] {
+    let mut timers = [Timer::new(Label::numbered(0), DEFAULT_MINUTES_TO_GO); TIMERS];
+    let mut index = 1;
+    while index < TIMERS {
+        timers[index].label = Label::numbered(index);
+        index += 1;
+    }
+    timers
+}
+
+pub struct TimeSaver {
+    state: TimeSaverState,
+    timers: [Timer; TIMERS],
+    /// Index of the timer on screen, being set, or paused.
+    focus: usize,
+    /// Index of the timer whose alarm is ringing, None for the alarm clock.
+    ringing: Option<usize>,
+    /// State the alarms interrupted, to go back to once they are all dismissed.
+    interrupted: TimeSaverState,
+    /// Seconds left to the timer on screen, rounded up (shown as they are when the countdown starts).
+    seconds_left: u32,
+    clock_setting: DateTime,
+    clock_field: Field,
+    alarm_time: DateTime,
+    alarm_field: Field,
+    /// Whether the RTC alarm is set, whatever is on screen, until it rings or is given up.
+    alarm_armed: bool,
+    /// Whether the alarm clock rang while another alarm was ringing, and still has to be shown.
+    alarm_clock_rang: bool,
+    pomodoro_settings: pomodoro::Settings,
+    pomodoro_field: pomodoro::Field,
+    pomodoro: Option<Pomodoro>,
+    /// Frame of the character animation while counting (standing or dancing).
+    standing: bool,
+    /// Phase of the blinking of the states that blink, on or off.
+    blink_on: bool,
+    /// Room for the deadlines of all the timers and of the Pomodoro, besides the (up to 3) actions of any state,
+    /// and a couple spare.
+    scheduler: Scheduler<Tick, { TIMERS + 6 }>,
+    /// Failure to schedule a timed action, reported once done with the event.
+    fault: Option<scheduler::Error>,
+}
+
+impl TimeSaver {
+    pub const fn new() -> Self {
+        TimeSaver {
+            state: TimeSaverState::Splash,
+            timers: default_timers(),
+            focus: 0,
+            ringing: None,
+            interrupted: TimeSaverState::Splash,
+            seconds_left: 0,
+            clock_setting: DEFAULT_DATE_TIME,
+            clock_field: Field::Year,
+            alarm_time: DEFAULT_DATE_TIME,
+            alarm_field: Field::Hour,
+            alarm_armed: false,
+            alarm_clock_rang: false,
+            pomodoro_settings: pomodoro::Settings::DEFAULT,
+            pomodoro_field: pomodoro::Field::Work,
+            pomodoro: None,
+            standing: false,
+            blink_on: true,
+            scheduler: Scheduler::new(),
+            fault: None,
+        }
+    }
+
+    /// Enter the splash screen, performing its one-time actions.
+    pub fn start(&mut self, now: Now) -> Effects {
+        let mut effects = Effects::new();
+        self.enter(TimeSaverState::Splash, now, &mut effects);
+        if let Some(error) = self.fault.take() {
+            emit(&mut effects, Effect::Fault(error));
+        }
+        effects
+    }
+
+    pub fn state(&self) -> TimeSaverState {
+        self.state
+    }
+
+    /// Minutes selected in the setting screen.
+    pub fn minutes_to_go(&self) -> u32 {
+        self.timers[self.focus].minutes
+    }
+
+    /// Seconds left to the countdown on screen, of a timer or of the Pomodoro.
+    pub fn seconds_left(&self) -> u32 {
+        self.seconds_left
+    }
+
+    /// Timer on screen, being set, or paused.
+    pub fn timer(&self) -> &Timer {
+        &self.timers[self.focus]
+    }
+
+    pub fn timers(&self) -> &[Timer; TIMERS] {
+        &self.timers
+    }
+
+    /// Number of timers counting down, paused or not.
+    pub fn active_timers(&self) -> usize {
+        timers::active(&self.timers)
+    }
+
+    /// Timer whose alarm is ringing, None for the alarm clock.
+    pub fn ringing(&self) -> Option<&Timer> {
+        self.ringing.map(|index| &self.timers[index])
+    }
+
+    /// Date and time being set, and the field being edited.
+    pub fn clock_setting(&self) -> (DateTime, Field) {
+        (self.clock_setting, self.clock_field)
+    }
+
+    /// Time of day of the alarm clock (hours and minutes), and the field being edited.
+    pub fn alarm_time(&self) -> (DateTime, Field) {
+        (self.alarm_time, self.alarm_field)
+    }
+
+    /// Pomodoro settings, and the field being edited.
+    pub fn pomodoro_settings(&self) -> (pomodoro::Settings, pomodoro::Field) {
+        (self.pomodoro_settings, self.pomodoro_field)
+    }
+
+    /// Pomodoro going on, if any.
+    pub fn pomodoro(&self) -> Option<&Pomodoro> {
+        self.pomodoro.as_ref()
+    }
+
+    /// Whether any timer or the Pomodoro is counting down, paused or not.
+    pub fn is_counting(&self) -> bool {
+        self.active_timers() > 0 || self.pomodoro.is_some()
+    }
+
+    pub fn standing(&self) -> bool {
+        self.standing
+    }
+
+    /// The backlight blinks while ringing, or alerting.
+    pub fn backlight_on(&self) -> bool {
+        !matches!(self.state, TimeSaverState::Alarm | TimeSaverState::PomodoroAlert) || self.blink_on
+    }
+
+    /// The time left blinks while paused.
+    pub fn time_shown(&self) -> bool {
+        self.state != TimeSaverState::Paused || self.blink_on
+    }
+
+    /// Rename the timer with the given index, returning whether it exists (and the screen must be redrawn).
+    pub fn rename(&mut self, index: usize, label: Label) -> bool {
+        match self.timers.get_mut(index) {
+            Some(timer) => {
+                timer.label = label;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Deadline of the next timed action, to sleep until then.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.scheduler.next_deadline()
+    }
+
+    /// React to an event, then perform the timed actions due by `now`.
+    pub fn handle(&mut self, event: Event, now: Now) -> Effects {
+        let mut effects = Effects::new();
+        if let Some(next_state) = self.on_event(event, now, &mut effects) {
+            self.enter(next_state, now, &mut effects);
+        }
+
+        while let Some(tick) = self.scheduler.poll(now.instant) {
+            if let Some(next_state) = self.on_tick(tick, now, &mut effects) {
+                self.enter(next_state, now, &mut effects);
+                break; // the timers of the new state are not due yet
+            }
+        }
+        if let Some(error) = self.fault.take() {
+            emit(&mut effects, Effect::Fault(error));
+        }
+        effects
+    }
+
+    /// Trigger state changes, returning the new state if any.
+    fn on_event(&mut self, event: Event, now: Now, effects: &mut Effects) -> Option<TimeSaverState> {
+        let (ui_event, at) = match event {
+            Event::Ui(ui_event, at) => (ui_event, at),
+            // The alarm clock rings once, whatever is on screen, after the alarms already ringing if any
+            Event::AlarmRang if self.alarm_armed => {
+                self.alarm_armed = false;
+                emit(effects, Effect::CancelAlarm); // the RTC would ring again the next day
+                emit(effects, Effect::Redraw);
+                if self.state == TimeSaverState::Alarm {
+                    self.alarm_clock_rang = true;
+                    return None;
+                }
+                self.ringing = None;
+                return Some(TimeSaverState::Alarm);
+            }
+            Event::AlarmRang | Event::Tick => return None,
+        };
+        // Turning the knob while pressing it only differs from plain turns while counting down
+        let ui_event = match ui_event {
+            UiEvent::HeldUp(steps) if self.state != TimeSaverState::Count => UiEvent::Up(steps),
+            UiEvent::HeldDown(steps) if self.state != TimeSaverState::Count => UiEvent::Down(steps),
+            _ => ui_event,
+        };
+        let steps = match ui_event {
+            UiEvent::Up(steps) | UiEvent::HeldUp(steps) => steps.min(i32::MAX as u32) as i32,
+            UiEvent::Down(steps) | UiEvent::HeldDown(steps) => -(steps.min(i32::MAX as u32) as i32),
+            _ => 0,
+        };
+
+        match (self.state, ui_event) {
+            // Back to the Pomodoro if going on, or set the first timer not running yet, if any
+            (TimeSaverState::Splash, UiEvent::Select) if self.pomodoro.is_some() => Some(TimeSaverState::Pomodoro),
+            (TimeSaverState::Splash, UiEvent::Select) => {
+                match self.timers.iter().position(|timer| timer.status == Status::Idle) {
+                    Some(index) => {
+                        self.focus = index;
+                        Some(TimeSaverState::Setting)
+                    }
+                    None => Some(TimeSaverState::Count),
+                }
+            }
+            (TimeSaverState::Splash, UiEvent::LongSelect) => Some(TimeSaverState::ClockSetting),
+
+            // Set the wall clock one field at a time, from the year to the minutes
+            (TimeSaverState::ClockSetting, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                self.clock_setting = self.clock_setting.adjust(self.clock_field, steps, YEARS);
+                emit(effects, Effect::Redraw);
+                None
+            }
+            (TimeSaverState::ClockSetting, UiEvent::Select) => match self.clock_field.next() {
+                Some(field) => {
+                    self.clock_field = field;
+                    emit(effects, Effect::Redraw);
+                    None
+                }
+                None => {
+                    // Seconds start from zero, at the confirmation of the minutes
+                    emit(effects, Effect::SetClock(DateTime { second: 0, ..self.clock_setting }));
+                    Some(TimeSaverState::Splash)
+                }
+            },
+            (TimeSaverState::ClockSetting, UiEvent::Back) => Some(TimeSaverState::Splash),
+
+            (TimeSaverState::Setting, UiEvent::Select) => {
+                let minutes = Duration::from_secs(self.timers[self.focus].minutes as u64 * 60);
+                self.run(self.focus, now.instant, minutes);
+                Some(TimeSaverState::Count)
+            }
+            (TimeSaverState::Setting, UiEvent::Back) => Some(self.overview()),
+
+            // Switch to a Pomodoro, then to an alarm at a time of day, which needs the wall clock to be set first
+            (TimeSaverState::Setting, UiEvent::LongSelect) => Some(TimeSaverState::PomodoroSetting),
+            (TimeSaverState::PomodoroSetting, UiEvent::LongSelect) => match now.wall_clock {
+                Some(_) => Some(TimeSaverState::AlarmClockSetting),
+                None => Some(TimeSaverState::ClockSetting),
+            },
+
+            // Select the minutes to go
+            (TimeSaverState::Setting, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                // The policy keeps the value within the allowed minutes
+                let minutes = &mut self.timers[self.focus].minutes;
+                *minutes = range::apply(&MINUTES_POLICY, *minutes as i32, steps) as u32;
+                emit(effects, Effect::Redraw);
+                None
+            }
+
+            // Scroll between the timers, going back to the most urgent one after a while
+            (TimeSaverState::Count, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                self.focus = (self.focus as i32 + steps).rem_euclid(TIMERS as i32) as usize;
+                self.follow_shown(now.instant);
+                self.schedule_refocus(now.instant);
+                emit(effects, Effect::Redraw);
+                None
+            }
+
+            // Pause the timer on screen, resume it if paused, or set it if not running
+            (TimeSaverState::Count | TimeSaverState::Adjusting, UiEvent::Select) => {
+                match self.timers[self.focus].status {
+                    Status::Running { end } => {
+                        // Paused at the click, however late it is handled
+                        self.scheduler.cancel(Tick::End(self.focus));
+                        let left = end.saturating_duration_since(at);
+                        self.timers[self.focus].status = Status::Paused { left };
+                        Some(TimeSaverState::Paused)
+                    }
+                    Status::Paused { left } => {
+                        self.run(self.focus, at, left);
+                        Some(TimeSaverState::Count)
+                    }
+                    Status::Idle | Status::Expired => Some(TimeSaverState::Setting),
+                }
+            }
+
+            // Add or remove minutes while the timer keeps running, turning the knob while pressing it at first, and
+            // going on with plain turns until back to scrolling after a while
+            (TimeSaverState::Count, UiEvent::HeldUp(_) | UiEvent::HeldDown(_))
+            | (TimeSaverState::Adjusting, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                if let Status::Running { end } = self.timers[self.focus].status {
+                    let left = add_minutes(end.saturating_duration_since(now.instant), steps);
+                    self.run(self.focus, now.instant, left);
+                    self.follow_shown(now.instant);
+                    self.schedule_refocus(now.instant);
+                    emit(effects, Effect::Redraw);
+                    if self.state == TimeSaverState::Count {
+                        return Some(TimeSaverState::Adjusting);
+                    }
+                }
+                None
+            }
+            (TimeSaverState::Adjusting, UiEvent::Back) => Some(TimeSaverState::Count),
+
+            // Resume the timer on screen, adding or removing minutes meanwhile if needed
+            (TimeSaverState::Paused, UiEvent::Select) => {
+                if let Status::Paused { left } = self.timers[self.focus].status {
+                    self.run(self.focus, at, left);
+                }
+                Some(TimeSaverState::Count)
+            }
+            (TimeSaverState::Paused, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                if let Status::Paused { left } = self.timers[self.focus].status {
+                    let left = add_minutes(left, steps);
+                    self.timers[self.focus].status = Status::Paused { left };
+                    self.seconds_left = whole_seconds(left);
+                    emit(effects, Effect::Redraw);
+                }
+                None
+            }
+
+            // Give up the timer on screen, back to the minutes selected, or leave the timers running
+            (TimeSaverState::Count | TimeSaverState::Paused, UiEvent::LongSelect | UiEvent::Back)
+            | (TimeSaverState::Adjusting, UiEvent::LongSelect) => {
+                if self.timers[self.focus].is_active() {
+                    self.stop(self.focus);
+                    Some(TimeSaverState::Setting)
+                } else if self.pomodoro.is_some() {
+                    Some(TimeSaverState::Pomodoro)
+                } else {
+                    Some(TimeSaverState::Splash)
+                }
+            }
+
+            // Select the time of day to ring at, hours first
+            (TimeSaverState::AlarmClockSetting, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                self.alarm_time = self.alarm_time.adjust(self.alarm_field, steps, YEARS);
+                emit(effects, Effect::Redraw);
+                None
+            }
+            (TimeSaverState::AlarmClockSetting, UiEvent::Select) => {
+                if self.alarm_field == Field::Hour {
+                    self.alarm_field = Field::Minute;
+                    emit(effects, Effect::Redraw);
+                    None
+                } else {
+                    // Woken up by the alarm, while the time left is only refreshed on screen
+                    self.alarm_armed = true;
+                    emit(effects, Effect::SetAlarm(self.alarm_time));
+                    Some(TimeSaverState::AlarmClock)
+                }
+            }
+            (TimeSaverState::AlarmClockSetting, UiEvent::Back | UiEvent::LongSelect) => Some(TimeSaverState::Setting),
+
+            (TimeSaverState::AlarmClock, UiEvent::Back | UiEvent::LongSelect) => {
+                self.alarm_armed = false;
+                emit(effects, Effect::CancelAlarm);
+                Some(TimeSaverState::Splash)
+            }
+
+            // Set the Pomodoro one field at a time, then start it
+            (TimeSaverState::PomodoroSetting, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                self.pomodoro_settings = self.pomodoro_settings.adjust(self.pomodoro_field, steps);
+                emit(effects, Effect::Redraw);
+                None
+            }
+            (TimeSaverState::PomodoroSetting, UiEvent::Select) => match self.pomodoro_field.next() {
+                Some(field) => {
+                    self.pomodoro_field = field;
+                    emit(effects, Effect::Redraw);
+                    None
+                }
+                None => {
+                    self.pomodoro = Some(Pomodoro { session: Session::FIRST, end: None });
+                    if !self.pomodoro_settings.click_to_start {
+                        self.start_phase(now.instant);
+                    }
+                    Some(TimeSaverState::Pomodoro)
+                }
+            },
+            (TimeSaverState::PomodoroSetting, UiEvent::Back) => Some(TimeSaverState::Setting),
+
+            // Start the phase waiting for a click, or give the Pomodoro up
+            (TimeSaverState::Pomodoro, UiEvent::Select) => {
+                if matches!(self.pomodoro, Some(Pomodoro { end: None, .. })) {
+                    self.start_phase(now.instant);
+                    self.follow_shown(now.instant);
+                    emit(effects, Effect::Redraw);
+                }
+                None
+            }
+            (TimeSaverState::Pomodoro, UiEvent::LongSelect | UiEvent::Back) => {
+                self.pomodoro = None;
+                self.scheduler.cancel(Tick::PhaseEnd);
+                Some(TimeSaverState::PomodoroSetting)
+            }
+            // Leave the Pomodoro running to see the timers counting down, or to set an idle one
+            (TimeSaverState::Pomodoro, UiEvent::Up(_) | UiEvent::Down(_)) => {
+                if self.active_timers() > 0 {
+                    return Some(TimeSaverState::Count);
+                }
+                let index = self.timers.iter().position(|timer| timer.status == Status::Idle)?;
+                self.focus = index;
+                Some(TimeSaverState::Setting)
+            }
+            (TimeSaverState::PomodoroAlert, UiEvent::Select | UiEvent::Back) => Some(TimeSaverState::Pomodoro),
+
+            // Dismiss the alarm, then the one of any other timer up meanwhile, and of the alarm clock last
+            (TimeSaverState::Alarm, UiEvent::Select | UiEvent::Back) => {
+                if let Some(index) = self.ringing {
+                    self.timers[index].status = Status::Idle;
+                }
+                match timers::expired(&self.timers) {
+                    Some(index) => {
+                        self.ringing = Some(index);
+                        Some(TimeSaverState::Alarm)
+                    }
+                    None if mem::take(&mut self.alarm_clock_rang) => {
+                        self.ringing = None;
+                        Some(TimeSaverState::Alarm)
+                    }
+                    // Back to the alarm clock, if still waiting for it
+                    None if self.interrupted == TimeSaverState::AlarmClock && self.alarm_armed => {
+                        Some(TimeSaverState::AlarmClock)
+                    }
+                    None => Some(self.overview()),
+                }
+            }
+
+            _ => None, // keep same state otherwise
+        }
+    }
+
+    /// Perform a timed action, returning the new state if any.
+    fn on_tick(&mut self, tick: Tick, now: Now, effects: &mut Effects) -> Option<TimeSaverState> {
+        match tick {
+            // Update remaining time each second, from the deadline so that no error builds up
+            Tick::Second => {
+                if let Some(end) = self.shown_end() {
+                    self.seconds_left = seconds_until(now.instant, end);
+                }
+            }
+
+            // Time is up, exactly at the deadline, whatever is on screen; alarms ringing meanwhile are queued
+            Tick::End(index) => {
+                self.timers[index].status = Status::Expired;
+                if self.state != TimeSaverState::Alarm {
+                    self.ringing = Some(index);
+                    return Some(TimeSaverState::Alarm);
+                }
+            }
+
+            // Character animation (bottom-right of the screen) at 2Hz, along with the LED
+            Tick::Animation => {
+                self.standing = !self.standing;
+                emit(effects, Effect::Led(self.standing));
+            }
+
+            // Blink LCD backlight, or the time left, at 2Hz
+            Tick::Blink => self.blink_on = !self.blink_on,
+
+            // Keep the wall clock on the splash screen, or the time left to the alarm clock, up to date
+            Tick::Clock => {}
+
+            // Back to scrolling, after adjusting the minutes of a timer
+            Tick::Refocus if self.state == TimeSaverState::Adjusting => return Some(TimeSaverState::Count),
+
+            // Back to the most urgent timer, after scrolling to another one
+            Tick::Refocus => {
+                self.focus = timers::most_urgent(&self.timers).unwrap_or(self.focus);
+                self.follow_shown(now.instant);
+            }
+
+            // Move on to the next phase of the Pomodoro, right from the end of the last one unless waiting for a
+            // click, with a short alert (unless an alarm is ringing)
+            Tick::PhaseEnd => {
+                if let Some(pomodoro) = &mut self.pomodoro {
+                    let end = pomodoro.end.unwrap_or(now.instant);
+                    *pomodoro = Pomodoro { session: pomodoro.session.next(&self.pomodoro_settings), end: None };
+                    if !self.pomodoro_settings.click_to_start {
+                        self.start_phase(end);
+                    }
+                    if self.state != TimeSaverState::Alarm {
+                        return Some(TimeSaverState::PomodoroAlert);
+                    }
+                }
+            }
+            Tick::AlertEnd => return Some(TimeSaverState::Pomodoro),
+        }
+        emit(effects, Effect::Redraw);
+        None
+    }
+
+    /// Move to a new state, performing its one-time actions.
+    fn enter(&mut self, state: TimeSaverState, now: Now, effects: &mut Effects) {
+        // Periodic actions only belong to the state they were scheduled by, unlike the deadlines of the countdowns
+        self.scheduler.cancel_where(|tick| !matches!(tick, Tick::End(_) | Tick::PhaseEnd));
+        if state == TimeSaverState::Alarm && self.state != TimeSaverState::Alarm {
+            self.interrupted = self.state;
+        }
+        self.state = state;
+        self.blink_on = true;
+        emit(effects, Effect::Redraw);
+
+        match state {
+            TimeSaverState::Splash => {
+                self.schedule_every(Tick::Clock, now.instant, Duration::from_secs(1), CatchUp::Skip);
+            }
+
+            TimeSaverState::ClockSetting => {
+                // Start from the current time, if any
+                self.clock_setting = now.wall_clock.unwrap_or(DEFAULT_DATE_TIME);
+                self.clock_field = Field::Year;
+            }
+
+            // Each timer keeps the minutes selected last
+            TimeSaverState::Setting => {}
+
+            TimeSaverState::Count => {
+                self.focus = timers::most_urgent(&self.timers).unwrap_or(self.focus);
+                self.follow_shown(now.instant);
+                self.schedule_every(Tick::Animation, now.instant, Duration::from_millis(500), CatchUp::Skip);
+            }
+
+            TimeSaverState::Adjusting => {
+                self.follow_shown(now.instant);
+                self.schedule_refocus(now.instant);
+            }
+
+            TimeSaverState::Paused => {
+                self.follow_shown(now.instant);
+                self.schedule_every(Tick::Blink, now.instant, Duration::from_millis(500), CatchUp::Skip);
+            }
+
+            TimeSaverState::AlarmClockSetting => {
+                // Start from the current time
+                self.alarm_time = now.wall_clock.unwrap_or(self.alarm_time);
+                self.alarm_field = Field::Hour;
+            }
+
+            TimeSaverState::AlarmClock => {
+                self.schedule_every(Tick::Clock, now.instant, Duration::from_secs(1), CatchUp::Skip);
+            }
+
+            TimeSaverState::Alarm => {
+                self.focus = self.ringing.unwrap_or(self.focus);
+                self.schedule_every(Tick::Blink, now.instant, Duration::from_millis(500), CatchUp::Skip);
+            }
+
+            TimeSaverState::PomodoroSetting => self.pomodoro_field = pomodoro::Field::Work,
+
+            TimeSaverState::Pomodoro => {
+                self.follow_shown(now.instant);
+                self.schedule_every(Tick::Animation, now.instant, Duration::from_millis(500), CatchUp::Skip);
+            }
+
+            TimeSaverState::PomodoroAlert => {
+                self.follow_shown(now.instant);
+                self.schedule_every(Tick::Blink, now.instant, Duration::from_millis(250), CatchUp::Skip);
+                self.schedule_after(Tick::AlertEnd, now.instant, POMODORO_ALERT);
+            }
+        }
+    }
+
+    /// Screen to go back to once done with a timer: the Pomodoro or the running timers, if any.
+    fn overview(&self) -> TimeSaverState {
+        if self.pomodoro.is_some() {
+            TimeSaverState::Pomodoro
+        } else if self.active_timers() > 0 {
+            TimeSaverState::Count
+        } else {
+            TimeSaverState::Splash
+        }
+    }
+
+    /// (Re)start counting down the given time left with a timer.
+    fn run(&mut self, index: usize, now: Instant, left: Duration) {
+        let end = now.saturating_add(left);
+        self.timers[index].status = Status::Running { end };
+        self.scheduler.cancel(Tick::End(index));
+        self.schedule_at(Tick::End(index), end);
+    }
+
+    fn stop(&mut self, index: usize) {
+        self.scheduler.cancel(Tick::End(index));
+        self.timers[index].status = Status::Idle;
+    }
+
+    /// Start the current phase of the Pomodoro, if any, from the given instant.
+    fn start_phase(&mut self, start: Instant) {
+        if let Some(pomodoro) = &mut self.pomodoro {
+            let minutes = self.pomodoro_settings.minutes(pomodoro.session.phase);
+            let end = start.saturating_add(Duration::from_secs(minutes as u64 * 60));
+            pomodoro.end = Some(end);
+            self.scheduler.cancel(Tick::PhaseEnd);
+            self.schedule_at(Tick::PhaseEnd, end);
+        }
+    }
+
+    /// Deadline of the countdown on screen, if running: of the Pomodoro in its screens, of a timer otherwise.
+    fn shown_end(&self) -> Option<Instant> {
+        match self.state {
+            TimeSaverState::Pomodoro | TimeSaverState::PomodoroAlert => self.pomodoro.and_then(|pomodoro| pomodoro.end),
+            _ => match self.timers[self.focus].status {
+                Status::Running { end } => Some(end),
+                _ => None,
+            },
+        }
+    }
+
+    /// Show the time left to the countdown on screen, ticking seconds while it runs.
+    fn follow_shown(&mut self, now: Instant) {
+        self.seconds_left = match (self.state, self.pomodoro) {
+            (TimeSaverState::Pomodoro | TimeSaverState::PomodoroAlert, Some(pomodoro)) => match pomodoro.end {
+                Some(end) => seconds_until(now, end),
+                // The whole phase, while waiting to start it
+                None => self.pomodoro_settings.minutes(pomodoro.session.phase) * 60,
+            },
+            _ => self.timers[self.focus].left(now).map(whole_seconds).unwrap_or(0),
+        };
+        self.scheduler.cancel(Tick::Second);
+
+        // Seconds are ticked in step with the deadline, so from a whole number of seconds before it; late ticks
+        // are just skipped, as the time left is worked out from the deadline anyway
+        if let Some(end) = self.shown_end() {
+            let second_ticks_start = end.saturating_sub(Duration::from_secs(self.seconds_left as u64));
+            self.schedule_every(Tick::Second, second_ticks_start, Duration::from_secs(1), CatchUp::Skip);
+        }
+    }
+
+    fn schedule_at(&mut self, tick: Tick, deadline: Instant) {
+        let result = self.scheduler.schedule_at(tick, deadline);
+        self.check(result);
+    }
+
+    fn schedule_after(&mut self, tick: Tick, now: Instant, delay: Duration) {
+        let result = self.scheduler.schedule_after(tick, now, delay);
+        self.check(result);
+    }
+
+    fn schedule_every(&mut self, tick: Tick, now: Instant, period: Duration, catch_up: CatchUp) {
+        let result = self.scheduler.schedule_every(tick, now, period, catch_up);
+        self.check(result);
+    }
+
+    /// (Re)start waiting to go back to the most urgent timer, or to scrolling.
+    fn schedule_refocus(&mut self, now: Instant) {
+        self.scheduler.cancel(Tick::Refocus);
+        self.schedule_after(Tick::Refocus, now, REFOCUS_DELAY);
+    }
+
+    /// Keep the failure to schedule a timed action, if any, to report it.
+    fn check(&mut self, result: Result<(), scheduler::Error>) {
+        if let Err(error) = result {
+            self.fault = Some(error);
+        }
+    }
+}
+
+impl Default for TimeSaver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
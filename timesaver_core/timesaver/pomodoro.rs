@@ -0,0 +1,109 @@
+//! Pomodoro technique: work sessions separated by short breaks, and by a long one after each cycle of sessions.
+
+use crate::encoder_interface::range::{self, Policy};
+use crate::millis::Instant;
+
+use super::MINUTES_POLICY;
+
+/// Work sessions per cycle that can be selected, shown with a single digit.
+const SESSIONS_POLICY: Policy = Policy::clamp(1, 9, 1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Work,
+    ShortBreak,
+    LongBreak,
+}
+
+/// Settings edited one at a time, from the length of the work sessions to whether to click to start.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Work,
+    ShortBreak,
+    LongBreak,
+    Sessions,
+    ClickToStart,
+}
+
+impl Field {
+    /// Field edited after this one, None if the last.
+    pub fn next(self) -> Option<Field> {
+        match self {
+            Field::Work => Some(Field::ShortBreak),
+            Field::ShortBreak => Some(Field::LongBreak),
+            Field::LongBreak => Some(Field::Sessions),
+            Field::Sessions => Some(Field::ClickToStart),
+            Field::ClickToStart => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Settings {
+    /// Minutes of each phase.
+    pub work: u32,
+    pub short_break: u32,
+    pub long_break: u32,
+    /// Work sessions per cycle, the last of which is followed by the long break.
+    pub sessions: u32,
+    /// Wait for a click before starting each phase, instead of moving on by itself.
+    pub click_to_start: bool,
+}
+
+impl Settings {
+    /// The classic 25 minutes of work, with 5 minute breaks and a 15 minute one every 4 sessions.
+    pub const DEFAULT: Settings =
+        Settings { work: 25, short_break: 5, long_break: 15, sessions: 4, click_to_start: false };
+
+    pub fn minutes(&self, phase: Phase) -> u32 {
+        match phase {
+            Phase::Work => self.work,
+            Phase::ShortBreak => self.short_break,
+            Phase::LongBreak => self.long_break,
+        }
+    }
+
+    /// Settings with a field adjusted by the given steps, within its range (any odd steps toggle the click).
+    pub fn adjust(self, field: Field, steps: i32) -> Settings {
+        let minutes = |minutes: u32| range::apply(&MINUTES_POLICY, minutes as i32, steps) as u32;
+        match field {
+            Field::Work => Settings { work: minutes(self.work), ..self },
+            Field::ShortBreak => Settings { short_break: minutes(self.short_break), ..self },
+            Field::LongBreak => Settings { long_break: minutes(self.long_break), ..self },
+            Field::Sessions => {
+                Settings { sessions: range::apply(&SESSIONS_POLICY, self.sessions as i32, steps) as u32, ..self }
+            }
+            Field::ClickToStart => Settings { click_to_start: self.click_to_start ^ (steps % 2 != 0), ..self },
+        }
+    }
+}
+
+/// Position in the cycle of sessions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Session {
+    pub phase: Phase,
+    /// Work session of the cycle, counting from 1 (breaks count as the session they follow).
+    pub number: u32,
+}
+
+impl Session {
+    pub const FIRST: Session = Session { phase: Phase::Work, number: 1 };
+
+    /// Session following this one: a break after working, the long one at the end of the cycle.
+    pub fn next(self, settings: &Settings) -> Session {
+        match self.phase {
+            Phase::Work if self.number >= settings.sessions => Session { phase: Phase::LongBreak, ..self },
+            Phase::Work => Session { phase: Phase::ShortBreak, ..self },
+            Phase::ShortBreak => Session { phase: Phase::Work, number: self.number + 1 },
+            Phase::LongBreak => Session::FIRST,
+        }
+    }
+}
+
+/// Pomodoro going on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pomodoro {
+    pub session: Session,
+    /// Deadline of the current phase, None while waiting for a click to start it.
+    pub end: Option<Instant>,
+}
@@ -0,0 +1,633 @@
+//! Scripted runs of the TimeSaver: UI events and the passing of time are fed as the main loop would, sleeping
+//! until each deadline, and the effects and states are checked along the way.
+
+use std::vec::Vec;
+
+use super::*;
+
+const MINUTE_MS: u64 = 60_000;
+
+const NOON: DateTime = DateTime::new(2024, 5, 17, 12, 0, 0);
+
+struct Run {
+    timesaver: TimeSaver,
+    now_ms: u64,
+    wall_clock: Option<DateTime>,
+    /// Every effect emitted so far, in order.
+    effects: Vec<Effect>,
+}
+
+impl Run {
+    fn start() -> Run {
+        Run::start_with_clock(None)
+    }
+
+    fn start_with_clock(wall_clock: Option<DateTime>) -> Run {
+        let mut run = Run { timesaver: TimeSaver::new(), now_ms: 0, wall_clock, effects: Vec::new() };
+        let effects = run.timesaver.start(run.now());
+        run.effects.extend(effects);
+        run
+    }
+
+    fn now(&self) -> Now {
+        Now { instant: Instant::from_millis(self.now_ms), wall_clock: self.wall_clock }
+    }
+
+    fn handle(&mut self, event: Event) -> Effects {
+        let effects = self.timesaver.handle(event, self.now());
+        self.effects.extend(effects.iter().copied());
+        effects
+    }
+
+    fn press(&mut self, ui_event: UiEvent) -> Effects {
+        let effects = self.handle(Event::Ui(ui_event, self.now().instant));
+        // The main loop does not sleep while there is something to do, e.g. the ticks due in a new state
+        self.handle(Event::Tick);
+        effects
+    }
+
+    fn press_all(&mut self, ui_events: &[UiEvent]) {
+        for ui_event in ui_events {
+            self.press(*ui_event);
+        }
+    }
+
+    /// Let the given time go by, waking up at each deadline in between.
+    fn wait_ms(&mut self, ms: u64) {
+        let until_ms = self.now_ms + ms;
+        while let Some(deadline) = self.timesaver.next_deadline().filter(|deadline| deadline.as_millis() <= until_ms) {
+            self.now_ms = self.now_ms.max(deadline.as_millis());
+            self.handle(Event::Tick);
+        }
+        self.now_ms = until_ms;
+        self.handle(Event::Tick);
+    }
+
+    fn state(&self) -> TimeSaverState {
+        self.timesaver.state()
+    }
+
+    fn take_effects(&mut self) -> Vec<Effect> {
+        core::mem::take(&mut self.effects)
+    }
+
+    /// Start the first idle timer for the given minutes, from the splash screen.
+    fn start_timer(&mut self, minutes: u32) {
+        assert_eq!(self.state(), TimeSaverState::Splash);
+        self.press(UiEvent::Select);
+        self.set_minutes(minutes);
+        self.press(UiEvent::Select);
+        assert_eq!(self.state(), TimeSaverState::Count);
+    }
+
+    fn set_minutes(&mut self, minutes: u32) {
+        assert_eq!(self.state(), TimeSaverState::Setting);
+        while self.timesaver.minutes_to_go() < minutes {
+            self.press(UiEvent::Up(1));
+        }
+        while self.timesaver.minutes_to_go() > minutes {
+            self.press(UiEvent::Down(1));
+        }
+    }
+}
+
+#[test]
+fn starts_on_the_splash_screen() {
+    let mut run = Run::start();
+    assert_eq!(run.state(), TimeSaverState::Splash);
+    assert_eq!(run.take_effects(), [Effect::Redraw]);
+    // The wall clock is refreshed every second
+    assert_eq!(run.timesaver.next_deadline(), Some(Instant::from_millis(1000)));
+}
+
+#[test]
+fn setting_keeps_the_minutes_in_range() {
+    let mut run = Run::start();
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Setting);
+    assert_eq!(run.timesaver.minutes_to_go(), DEFAULT_MINUTES_TO_GO);
+
+    run.press_all(&[UiEvent::Up(1), UiEvent::Up(1), UiEvent::Up(1)]);
+    assert_eq!(run.timesaver.minutes_to_go(), DEFAULT_MINUTES_TO_GO + 3);
+    for _ in 0..30 {
+        run.press(UiEvent::Down(1));
+    }
+    assert_eq!(run.timesaver.minutes_to_go(), 1);
+
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+}
+
+#[test]
+fn countdown_ticks_seconds_then_rings() {
+    let mut run = Run::start();
+    run.start_timer(2);
+    assert_eq!(run.timesaver.seconds_left(), 120);
+    assert_eq!(run.timesaver.timer().status, Status::Running { end: Instant::from_millis(2 * MINUTE_MS) });
+
+    run.wait_ms(1000);
+    assert_eq!(run.timesaver.seconds_left(), 119);
+    run.wait_ms(60_500);
+    assert_eq!(run.timesaver.seconds_left(), 59);
+
+    run.take_effects();
+    run.wait_ms(58_499);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    run.wait_ms(1);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert_eq!(run.timesaver.ringing().map(|timer| timer.status), Some(Status::Expired));
+    // The character danced along with the LED meanwhile
+    assert!(run.take_effects().contains(&Effect::Led(true)));
+
+    // The backlight blinks until dismissed
+    run.wait_ms(500);
+    assert!(!run.timesaver.backlight_on());
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+    assert!(run.timesaver.backlight_on());
+    assert_eq!(run.timesaver.timers()[0].status, Status::Idle);
+    assert_eq!(run.timesaver.timers()[0].minutes, 2);
+}
+
+#[test]
+fn late_wakeup_rings_at_the_deadline() {
+    let mut run = Run::start();
+    run.start_timer(1);
+
+    // Woken up long after the deadline, e.g. by an input
+    run.now_ms = 5 * MINUTE_MS;
+    run.handle(Event::Tick);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+}
+
+#[test]
+fn a_single_click_pauses_the_timer_on_screen() {
+    let mut run = Run::start();
+    run.start_timer(5);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    run.wait_ms(1000);
+
+    // One short click, with no other input before or after it
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Paused);
+    assert_eq!(run.timesaver.timer().status, Status::Paused { left: Duration::from_secs(5 * 60 - 1) });
+}
+
+#[test]
+fn pause_and_resume_keep_the_time_left() {
+    let mut run = Run::start();
+    run.start_timer(2);
+    run.wait_ms(30_000);
+
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Paused);
+    assert_eq!(run.timesaver.timer().status, Status::Paused { left: Duration::from_secs(90) });
+
+    // Time does not go by while paused, the time left blinks instead
+    run.wait_ms(10 * MINUTE_MS);
+    assert_eq!(run.timesaver.seconds_left(), 90);
+    run.wait_ms(500);
+    assert!(!run.timesaver.time_shown());
+
+    run.press(UiEvent::Up(1));
+    assert_eq!(run.timesaver.seconds_left(), 150);
+    run.press_all(&[UiEvent::Down(1), UiEvent::Down(1), UiEvent::Down(1)]);
+    assert_eq!(run.timesaver.seconds_left(), 60, "a minute is left at least");
+
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    assert!(run.timesaver.time_shown());
+    run.wait_ms(59_999);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    run.wait_ms(1);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+}
+
+#[test]
+fn fast_turns_move_by_all_their_steps_at_once() {
+    let mut run = Run::start();
+    run.press(UiEvent::Select);
+    let minutes = run.timesaver.minutes_to_go();
+    assert_eq!(run.press(UiEvent::Up(5)), [Effect::Redraw]);
+    assert_eq!(run.timesaver.minutes_to_go(), minutes + 5);
+    run.press(UiEvent::Down(7));
+    assert_eq!(run.timesaver.minutes_to_go(), minutes - 2);
+    // Pressing the knob meanwhile makes no difference here
+    run.press(UiEvent::HeldUp(3));
+    assert_eq!(run.timesaver.minutes_to_go(), minutes + 1);
+}
+
+#[test]
+fn timers_resume_at_the_click_however_late_it_is_handled() {
+    let mut run = Run::start();
+    run.start_timer(10);
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Paused);
+
+    // Clicked 30 s before the main loop got to it
+    run.wait_ms(MINUTE_MS);
+    run.handle(Event::Ui(UiEvent::Select, Instant::from_millis(run.now_ms - 30_000)));
+    assert_eq!(run.state(), TimeSaverState::Count);
+    assert_eq!(run.timesaver.seconds_left(), 10 * 60 - 30);
+}
+
+#[test]
+fn minutes_are_adjusted_while_running() {
+    let mut run = Run::start();
+    run.start_timer(2);
+    run.wait_ms(30_000);
+
+    // Turning the knob while pressing it, then going on with plain turns
+    run.press(UiEvent::HeldUp(1));
+    assert_eq!(run.state(), TimeSaverState::Adjusting);
+    run.press(UiEvent::Up(1));
+    assert_eq!(run.timesaver.seconds_left(), 210);
+    run.press(UiEvent::Down(1));
+    assert_eq!(run.timesaver.timer().status, Status::Running { end: Instant::from_millis(3 * MINUTE_MS) });
+
+    // It keeps running meanwhile, and goes back to scrolling after a while without turning the knob
+    run.wait_ms(REFOCUS_DELAY.as_millis() as u64 - 1);
+    assert_eq!(run.state(), TimeSaverState::Adjusting);
+    assert_eq!(run.timesaver.seconds_left(), 146);
+    run.wait_ms(1);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    run.wait_ms(3 * MINUTE_MS - run.now_ms);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+
+    // Back to scrolling straight away too, pausing or giving the timer up
+    run.press(UiEvent::Select);
+    run.start_timer(2);
+    run.press_all(&[UiEvent::HeldDown(1), UiEvent::Back]);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    run.press_all(&[UiEvent::HeldDown(1), UiEvent::Select]);
+    assert_eq!(run.state(), TimeSaverState::Paused);
+    run.press_all(&[UiEvent::Select, UiEvent::HeldUp(1), UiEvent::LongSelect]);
+    assert_eq!(run.state(), TimeSaverState::Setting);
+    assert_eq!(run.timesaver.timer().status, Status::Idle);
+}
+
+#[test]
+fn giving_up_goes_back_to_the_minutes() {
+    let mut run = Run::start();
+    run.start_timer(5);
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::Setting);
+    assert_eq!(run.timesaver.timer().status, Status::Idle);
+    assert_eq!(run.timesaver.minutes_to_go(), 5);
+
+    // The deadline went along with the timer
+    run.press(UiEvent::Back);
+    run.wait_ms(10 * MINUTE_MS);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+}
+
+#[test]
+fn timers_run_side_by_side() {
+    let mut run = Run::start();
+    run.start_timer(3);
+
+    // Scroll to the next timer, idle, and start it for less
+    run.press(UiEvent::Up(1));
+    assert_eq!(run.timesaver.timer().label.as_str(), "Timer 2");
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Setting);
+    run.set_minutes(1);
+    run.press(UiEvent::Select);
+    assert_eq!(run.timesaver.active_timers(), 2);
+    assert_eq!(run.timesaver.timer().label.as_str(), "Timer 2", "the most urgent is on screen");
+
+    // Scrolling away comes back to the most urgent after a while
+    run.press(UiEvent::Down(1));
+    assert_eq!(run.timesaver.timer().label.as_str(), "Timer 1");
+    run.wait_ms(REFOCUS_DELAY.as_millis() as u64);
+    assert_eq!(run.timesaver.timer().label.as_str(), "Timer 2");
+
+    // Each rings on its own
+    run.wait_ms(MINUTE_MS);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert_eq!(run.timesaver.ringing().unwrap().label.as_str(), "Timer 2");
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Count);
+    assert_eq!(run.timesaver.timer().label.as_str(), "Timer 1");
+    run.wait_ms(2 * MINUTE_MS);
+    assert_eq!(run.timesaver.ringing().unwrap().label.as_str(), "Timer 1");
+}
+
+#[test]
+fn alarms_ringing_together_are_queued() {
+    let mut run = Run::start();
+    run.start_timer(1);
+    run.press(UiEvent::Up(1));
+    run.press(UiEvent::Select);
+    run.set_minutes(1);
+    run.wait_ms(10_000);
+    run.press(UiEvent::Select);
+
+    run.wait_ms(MINUTE_MS);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert_eq!(run.timesaver.ringing().unwrap().label.as_str(), "Timer 1");
+
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert_eq!(run.timesaver.ringing().unwrap().label.as_str(), "Timer 2");
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+    assert_eq!(run.timesaver.active_timers(), 0);
+}
+
+#[test]
+fn renaming_a_timer() {
+    let mut run = Run::start();
+    assert!(run.timesaver.rename(1, Label::new("Pasta").unwrap()));
+    assert_eq!(run.timesaver.timers()[1].label.as_str(), "Pasta");
+
+    // Timers that do not exist are left alone
+    assert!(!run.timesaver.rename(TIMERS, Label::new("Rice").unwrap()));
+}
+
+#[test]
+fn clock_is_set_one_field_at_a_time() {
+    let mut run = Run::start_with_clock(Some(NOON));
+    run.press(UiEvent::LongSelect);
+    assert_eq!(run.state(), TimeSaverState::ClockSetting);
+    assert_eq!(run.timesaver.clock_setting(), (NOON, Field::Year));
+
+    run.press(UiEvent::Up(1));
+    run.press(UiEvent::Select);
+    run.press_all(&[UiEvent::Down(1), UiEvent::Down(1), UiEvent::Down(1), UiEvent::Down(1), UiEvent::Down(1)]);
+    assert_eq!(run.timesaver.clock_setting(), (DateTime::new(2025, 12, 17, 12, 0, 0), Field::Month));
+    run.press_all(&[UiEvent::Select, UiEvent::Select, UiEvent::Select, UiEvent::Up(1)]);
+    assert_eq!(run.timesaver.clock_setting().1, Field::Minute);
+
+    run.take_effects();
+    run.wall_clock = Some(DateTime { second: 42, ..NOON });
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+    assert!(run.take_effects().contains(&Effect::SetClock(DateTime::new(2025, 12, 17, 12, 1, 0))));
+}
+
+#[test]
+fn alarm_clock_needs_the_wall_clock() {
+    let mut run = Run::start();
+    run.press_all(&[UiEvent::Select, UiEvent::LongSelect]);
+    assert_eq!(run.state(), TimeSaverState::PomodoroSetting);
+    run.press(UiEvent::LongSelect);
+    assert_eq!(run.state(), TimeSaverState::ClockSetting);
+}
+
+#[test]
+fn alarm_clock_rings_at_the_time_of_day() {
+    let mut run = Run::start_with_clock(Some(NOON));
+    run.press_all(&[UiEvent::Select, UiEvent::LongSelect, UiEvent::LongSelect]);
+    assert_eq!(run.state(), TimeSaverState::AlarmClockSetting);
+    assert_eq!(run.timesaver.alarm_time(), (NOON, Field::Hour));
+
+    run.press_all(&[UiEvent::Down(1), UiEvent::Select, UiEvent::Up(1), UiEvent::Up(1)]);
+    assert_eq!(run.timesaver.alarm_time(), (DateTime::new(2024, 5, 17, 11, 2, 0), Field::Minute));
+    run.take_effects();
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::AlarmClock);
+    assert!(run.take_effects().contains(&Effect::SetAlarm(DateTime::new(2024, 5, 17, 11, 2, 0))));
+
+    run.handle(Event::AlarmRang);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert!(run.timesaver.ringing().is_none());
+    // It rings once, rather than every day
+    assert!(run.take_effects().contains(&Effect::CancelAlarm));
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+}
+
+/// Set the alarm clock at 12:30 from the setting screen, then wait for it.
+fn set_alarm_clock(run: &mut Run) {
+    assert_eq!(run.state(), TimeSaverState::Setting);
+    run.press_all(&[UiEvent::LongSelect, UiEvent::LongSelect, UiEvent::Select]);
+    for _ in 0..30 {
+        run.press(UiEvent::Up(1));
+    }
+    run.take_effects();
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::AlarmClock);
+    assert_eq!(run.take_effects(), [Effect::SetAlarm(DateTime::new(2024, 5, 17, 12, 30, 0)), Effect::Redraw]);
+}
+
+#[test]
+fn alarm_clock_stays_armed_while_a_timer_rings() {
+    let mut run = Run::start_with_clock(Some(NOON));
+    run.start_timer(1);
+    run.press_all(&[UiEvent::Up(1), UiEvent::Select]);
+    set_alarm_clock(&mut run);
+
+    run.wait_ms(MINUTE_MS);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert_eq!(run.timesaver.ringing().unwrap().label.as_str(), "Timer 1");
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::AlarmClock, "back to waiting for the alarm clock");
+    assert!(!run.take_effects().contains(&Effect::CancelAlarm));
+
+    run.handle(Event::AlarmRang);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert!(run.timesaver.ringing().is_none());
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+}
+
+#[test]
+fn alarm_clock_ringing_with_a_timer_is_queued() {
+    let mut run = Run::start_with_clock(Some(NOON));
+    run.start_timer(1);
+    run.press_all(&[UiEvent::Up(1), UiEvent::Select]);
+    set_alarm_clock(&mut run);
+
+    run.wait_ms(MINUTE_MS);
+    run.handle(Event::AlarmRang);
+    assert!(run.take_effects().contains(&Effect::CancelAlarm));
+    assert_eq!(run.timesaver.ringing().unwrap().label.as_str(), "Timer 1");
+
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert!(run.timesaver.ringing().is_none());
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+}
+
+#[test]
+fn alarm_clock_keeps_ringing_away_from_its_screen() {
+    let mut run = Run::start_with_clock(Some(NOON));
+    run.press(UiEvent::Select);
+    set_alarm_clock(&mut run);
+
+    // A Pomodoro phase ending takes the screen over, leaving the alarm set
+    run.timesaver.pomodoro = Some(Pomodoro { session: Session::FIRST, end: None });
+    run.timesaver.start_phase(Instant::from_millis(run.now_ms));
+    run.wait_ms(25 * MINUTE_MS);
+    assert_eq!(run.state(), TimeSaverState::PomodoroAlert);
+    assert!(!run.take_effects().contains(&Effect::CancelAlarm));
+    assert!(run.timesaver.alarm_armed);
+
+    run.handle(Event::AlarmRang);
+    assert_eq!(run.state(), TimeSaverState::Alarm);
+    assert!(run.timesaver.ringing().is_none());
+}
+
+#[test]
+fn giving_up_the_alarm_clock_cancels_it() {
+    let mut run = Run::start_with_clock(Some(NOON));
+    run.press(UiEvent::Select);
+    set_alarm_clock(&mut run);
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+    assert!(run.take_effects().contains(&Effect::CancelAlarm));
+
+    // A late alarm is ignored
+    let effects = run.handle(Event::AlarmRang);
+    assert!(effects.is_empty());
+    assert_eq!(run.state(), TimeSaverState::Splash);
+}
+
+#[test]
+fn pomodoro_cycles_through_its_phases() {
+    let mut run = Run::start();
+    run.press_all(&[UiEvent::Select, UiEvent::LongSelect]);
+    assert_eq!(run.timesaver.pomodoro_settings(), (pomodoro::Settings::DEFAULT, pomodoro::Field::Work));
+
+    // 2 sessions of 10 minutes, 1 minute breaks and a 3 minute long one
+    let steps: [(pomodoro::Field, i32); 4] = [
+        (pomodoro::Field::Work, 10 - 25),
+        (pomodoro::Field::ShortBreak, 1 - 5),
+        (pomodoro::Field::LongBreak, 3 - 15),
+        (pomodoro::Field::Sessions, 2 - 4),
+    ];
+    for (field, steps) in steps {
+        assert_eq!(run.timesaver.pomodoro_settings().1, field);
+        let key = if steps > 0 { UiEvent::Up(1) } else { UiEvent::Down(1) };
+        for _ in 0..steps.abs() {
+            run.press(key);
+        }
+        run.press(UiEvent::Select);
+    }
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+    assert_eq!(run.timesaver.seconds_left(), 10 * 60);
+
+    let phases = [
+        (pomodoro::Phase::ShortBreak, 1, 10),
+        (pomodoro::Phase::Work, 2, 1),
+        (pomodoro::Phase::LongBreak, 2, 10),
+        (pomodoro::Phase::Work, 1, 3),
+    ];
+    let mut phase_end_ms = 0;
+    for (phase, number, after_minutes) in phases {
+        phase_end_ms += after_minutes * MINUTE_MS;
+        run.wait_ms(phase_end_ms - run.now_ms);
+        assert_eq!(run.state(), TimeSaverState::PomodoroAlert);
+        assert_eq!(run.timesaver.pomodoro().unwrap().session, Session { phase, number });
+        assert!(run.timesaver.backlight_on());
+        run.wait_ms(250);
+        assert!(!run.timesaver.backlight_on());
+
+        // The alert only lasts a while, and the phase started right at the end of the previous one
+        run.wait_ms(POMODORO_ALERT.as_millis() as u64 - 250);
+        assert_eq!(run.state(), TimeSaverState::Pomodoro);
+        let minutes = run.timesaver.pomodoro_settings().0.minutes(phase);
+        assert_eq!(run.timesaver.seconds_left() as u64, minutes as u64 * 60 - POMODORO_ALERT.as_secs());
+    }
+}
+
+#[test]
+fn pomodoro_waits_for_a_click_to_start_each_phase() {
+    let mut run = Run::start();
+    run.press_all(&[UiEvent::Select, UiEvent::LongSelect]);
+    run.press_all(&[UiEvent::Select, UiEvent::Select, UiEvent::Select, UiEvent::Select, UiEvent::Up(1)]);
+    assert!(run.timesaver.pomodoro_settings().0.click_to_start);
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+    assert_eq!(run.timesaver.pomodoro().unwrap().end, None);
+
+    // Nothing happens until started
+    run.wait_ms(60 * MINUTE_MS);
+    assert_eq!(run.timesaver.seconds_left(), 25 * 60);
+    run.press(UiEvent::Select);
+    run.wait_ms(25 * MINUTE_MS);
+    assert_eq!(run.state(), TimeSaverState::PomodoroAlert);
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+    let short_break = Session { phase: pomodoro::Phase::ShortBreak, number: 1 };
+    assert_eq!(run.timesaver.pomodoro(), Some(&Pomodoro { session: short_break, end: None }));
+
+    // Giving it up goes back to its settings
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::PomodoroSetting);
+    assert!(run.timesaver.pomodoro().is_none());
+}
+
+#[test]
+fn timers_are_set_while_a_pomodoro_runs() {
+    let mut run = Run::start();
+    run.press_all(&[UiEvent::Select, UiEvent::LongSelect]);
+    run.press_all(&[UiEvent::Select; 5]);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+
+    // An idle timer is set, or not, then back to the Pomodoro
+    run.press(UiEvent::Up(1));
+    assert_eq!(run.state(), TimeSaverState::Setting);
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+    run.press(UiEvent::Down(1));
+    run.set_minutes(30);
+    run.press(UiEvent::Select);
+    assert_eq!(run.state(), TimeSaverState::Count);
+
+    // The end of the work phase takes the screen over, then the countdown is a click away
+    run.wait_ms(25 * MINUTE_MS + POMODORO_ALERT.as_millis() as u64);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+    run.press(UiEvent::Down(1));
+    assert_eq!(run.state(), TimeSaverState::Count);
+    assert!(run.timesaver.timers[0].is_active());
+
+    // Away from a running timer, back goes to the Pomodoro rather than giving it up
+    run.timesaver.focus = 1;
+    run.press(UiEvent::Back);
+    assert_eq!(run.state(), TimeSaverState::Pomodoro);
+    assert!(run.timesaver.timers[0].is_active());
+}
+
+#[test]
+fn scheduling_failures_are_reported() {
+    let mut run = Run::start();
+    run.take_effects();
+    // Back on the splash screen, the clock cannot be updated any more
+    run.now_ms = u64::MAX - 500;
+    run.press_all(&[UiEvent::Select, UiEvent::Back]);
+    assert_eq!(run.state(), TimeSaverState::Splash);
+    assert!(run.take_effects().contains(&Effect::Fault(scheduler::Error::Overflow)));
+}
+
+#[test]
+fn effects_are_emitted_once_per_kind() {
+    let mut effects = Effects::new();
+    emit(&mut effects, Effect::Led(true));
+    emit(&mut effects, Effect::Redraw);
+    emit(&mut effects, Effect::Led(false));
+    emit(&mut effects, Effect::Redraw);
+    assert_eq!(effects, [Effect::Led(false), Effect::Redraw]);
+}
+
+#[test]
+fn adding_minutes_keeps_the_time_left_in_range() {
+    let minute = Duration::from_secs(60);
+    assert_eq!(add_minutes(Duration::from_secs(90), 1), Duration::from_secs(150));
+    assert_eq!(add_minutes(Duration::from_secs(90), -1), minute);
+    // Less than a minute is left alone, rather than made longer
+    assert_eq!(add_minutes(Duration::from_secs(20), -1), Duration::from_secs(20));
+    assert_eq!(add_minutes(minute * MAX_MINUTES, 1), minute * MAX_MINUTES);
+    assert_eq!(add_minutes(minute * (MAX_MINUTES + 5), 1), minute * (MAX_MINUTES + 5));
+}
+
+#[test]
+fn seconds_are_rounded_up() {
+    assert_eq!(whole_seconds(Duration::ZERO), 0);
+    assert_eq!(whole_seconds(Duration::from_millis(1)), 1);
+    assert_eq!(whole_seconds(Duration::from_millis(59_001)), 60);
+    assert_eq!(whole_seconds(Duration::from_secs(60)), 60);
+}
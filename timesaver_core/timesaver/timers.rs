@@ -0,0 +1,101 @@
+//! Countdown timers of the TimeSaver, several of which can run at once, each with its own label.
+
+use core::time::Duration;
+
+use crate::millis::Instant;
+
+/// Longest label, fitting on a row next to the count of timers running.
+pub const LABEL_LEN: usize = 10;
+
+/// Name of a timer, in printable ASCII (the only characters all displays have).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Label {
+    bytes: [u8; LABEL_LEN],
+    len: u8,
+}
+
+impl Label {
+    /// Label with the given text, if short enough and printable.
+    pub fn new(text: &str) -> Option<Label> {
+        let text = text.trim();
+        let printable = text.bytes().all(|byte| byte.is_ascii_graphic() || byte == b' ');
+        if text.is_empty() || text.len() > LABEL_LEN || !printable {
+            return None;
+        }
+        let mut bytes = [b' '; LABEL_LEN];
+        bytes[..text.len()].copy_from_slice(text.as_bytes());
+        Some(Label { bytes, len: text.len() as u8 })
+    }
+
+    /// Default label of the timer with the given index (up to 8), counting from 1 as shown to users.
+    pub const fn numbered(index: usize) -> Label {
+        let mut bytes = *b"Timer     ";
+        bytes[6] = b'1' + index as u8;
+        Label { bytes, len: 7 }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only ever built from ASCII
+        core::str::from_utf8(&self.bytes[..self.len as usize]).unwrap_or("")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Idle,
+    Running { end: Instant },
+    Paused { left: Duration },
+    /// Time is up, and the alarm not dismissed yet.
+    Expired,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timer {
+    pub label: Label,
+    /// Minutes selected, kept for the next time the timer is started.
+    pub minutes: u32,
+    pub status: Status,
+}
+
+impl Timer {
+    pub const fn new(label: Label, minutes: u32) -> Self {
+        Timer { label, minutes, status: Status::Idle }
+    }
+
+    /// Counting down, paused or not.
+    pub fn is_active(&self) -> bool {
+        matches!(self.status, Status::Running { .. } | Status::Paused { .. })
+    }
+
+    /// Time left at `now`, if active.
+    pub fn left(&self, now: Instant) -> Option<Duration> {
+        match self.status {
+            Status::Running { end } => Some(end.saturating_duration_since(now)),
+            Status::Paused { left } => Some(left),
+            Status::Idle | Status::Expired => None,
+        }
+    }
+}
+
+/// Index of the timer running out first, if any is running.
+pub fn most_urgent(timers: &[Timer]) -> Option<usize> {
+    timers
+        .iter()
+        .enumerate()
+        .filter_map(|(index, timer)| match timer.status {
+            Status::Running { end } => Some((end, index)),
+            _ => None,
+        })
+        .min()
+        .map(|(_, index)| index)
+}
+
+/// Number of timers counting down, paused or not.
+pub fn active(timers: &[Timer]) -> usize {
+    timers.iter().filter(|timer| timer.is_active()).count()
+}
+
+/// Index of the first timer whose alarm has not been dismissed yet, if any.
+pub fn expired(timers: &[Timer]) -> Option<usize> {
+    timers.iter().position(|timer| timer.status == Status::Expired)
+}
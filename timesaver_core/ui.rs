@@ -0,0 +1,405 @@
+//! UI
+//! Screens of the TimeSaver, drawn on any character display with at least 2 rows of 16 columns.
+//!
+//! The whole screen is rendered from the state of the TimeSaver, e.g. into a `TextBuffer`, and only what
+//! changed is then copied to the actual display (see `copy`), as writing to it is slow.
+
+use alloc::format;
+
+use lcd1602::custom_characters::{HEART_FULL, MAN_DANCING, MAN_STANDING};
+use lcd1602::{CharDisplay, TextBuffer};
+
+use crate::clock::calendar::{DateTime, Field};
+use crate::timesaver::pomodoro::{self, Phase, Session};
+use crate::timesaver::timers::{Status, Timer};
+use crate::timesaver::{TimeSaver, TimeSaverState};
+
+/// Draw the screen of the current state of the TimeSaver, given the wall clock time.
+pub fn render<D: CharDisplay>(
+    display: &mut D,
+    timesaver: &TimeSaver,
+    wall_clock: Option<DateTime>,
+) -> Result<(), D::Error> {
+    match timesaver.state() {
+        TimeSaverState::Splash => {
+            show_splash(display)?;
+            update_clock(display, wall_clock)?;
+        }
+        TimeSaverState::ClockSetting => {
+            let (date_time, field) = timesaver.clock_setting();
+            show_clock_setting(display, field)?;
+            update_clock_setting(display, &date_time)?;
+        }
+        TimeSaverState::Setting => {
+            show_setting(display, timesaver.timer().label.as_str())?;
+            update_setting(display, timesaver.minutes_to_go())?;
+        }
+        TimeSaverState::Count => {
+            let timer = timesaver.timer();
+            show_count(display, timer.label.as_str(), timesaver.active_timers())?;
+            update_timer(display, timer, timesaver.seconds_left())?;
+            if let Status::Running { .. } = timer.status {
+                update_animation(display, timesaver.standing())?;
+            }
+        }
+        TimeSaverState::Adjusting => {
+            show_adjusting(display, timesaver.timer().label.as_str(), timesaver.seconds_left())?;
+        }
+        TimeSaverState::Paused => show_paused(display, timesaver.seconds_left(), timesaver.time_shown())?,
+        TimeSaverState::AlarmClockSetting => {
+            let (time, field) = timesaver.alarm_time();
+            show_alarm_clock_setting(display, field)?;
+            update_alarm_clock_setting(display, &time)?;
+        }
+        TimeSaverState::AlarmClock => {
+            let (time, _) = timesaver.alarm_time();
+            show_alarm_clock(display, &time)?;
+            if let Some(now) = wall_clock {
+                update_alarm_clock(display, now.seconds_until(time.hour, time.minute))?;
+            }
+        }
+        TimeSaverState::Alarm => show_alarm(display, timesaver.ringing().map(|timer| timer.label.as_str()))?,
+        TimeSaverState::PomodoroSetting => {
+            let (settings, field) = timesaver.pomodoro_settings();
+            show_pomodoro_setting(display, field)?;
+            update_pomodoro_setting(display, &settings, field)?;
+        }
+        TimeSaverState::Pomodoro => {
+            if let Some(pomodoro) = timesaver.pomodoro() {
+                let (settings, _) = timesaver.pomodoro_settings();
+                show_pomodoro(display, &pomodoro.session, settings.sessions)?;
+                display.set_cursor(1, 0)?;
+                if pomodoro.end.is_some() {
+                    display.print(&format!("{} left", format_duration(timesaver.seconds_left())))?;
+                    update_animation(display, timesaver.standing())?;
+                } else {
+                    display.print("Click to start")?;
+                }
+            }
+        }
+        TimeSaverState::PomodoroAlert => {
+            if let Some(pomodoro) = timesaver.pomodoro() {
+                let (settings, _) = timesaver.pomodoro_settings();
+                show_pomodoro_alert(display, &pomodoro.session, settings.sessions)?;
+            }
+        }
+    }
+    display.set_backlight(timesaver.backlight_on())
+}
+
+/// Copy a rendered screen to a display, only writing the rows (and the backlight) that differ from the screen
+/// copied before, which is updated.
+pub fn copy<D: CharDisplay, const ROWS: usize, const COLUMNS: usize>(
+    display: &mut D,
+    screen: &TextBuffer<ROWS, COLUMNS>,
+    copied: &mut TextBuffer<ROWS, COLUMNS>,
+) -> Result<(), D::Error> {
+    for row in 0..ROWS {
+        if screen.row(row) == copied.row(row) {
+            continue;
+        }
+        display.set_cursor(row as u8, 0)?;
+        copied.set_cursor(row as u8, 0).unwrap_or(());
+        for &code in screen.row(row) {
+            // Custom glyphs at [0-7], printable ASCII otherwise
+            if code < 8 {
+                display.write_custom_char(code)?;
+                copied.write_custom_char(code).unwrap_or(());
+            } else {
+                let text = [code];
+                let text = core::str::from_utf8(&text).unwrap_or("?");
+                display.print(text)?;
+                copied.print(text).unwrap_or(());
+            }
+        }
+    }
+
+    if screen.backlight() != copied.backlight() {
+        display.set_backlight(screen.backlight())?;
+        copied.set_backlight(screen.backlight()).unwrap_or(());
+    }
+    Ok(())
+}
+
+pub fn show_splash<D: CharDisplay>(display: &mut D) -> Result<(), D::Error> {
+    display.clear()?;
+    display.print("Save your time ")?;
+    display.write_custom_char(HEART_FULL)
+}
+
+/// Print the wall clock below the splash message, or a hint if it has never been set.
+pub fn update_clock<D: CharDisplay>(display: &mut D, date_time: Option<DateTime>) -> Result<(), D::Error> {
+    display.set_cursor(1, 0)?;
+    match date_time {
+        Some(date_time) => display.print(&format_date_time(&date_time)),
+        None => display.print("Clock not set"),
+    }
+}
+
+/// `YYYY-MM-DD hh:mm`, exactly 16 characters.
+fn format_date_time(date_time: &DateTime) -> alloc::string::String {
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        date_time.year, date_time.month, date_time.day, date_time.hour, date_time.minute
+    )
+}
+
+pub fn show_clock_setting<D: CharDisplay>(display: &mut D, field: Field) -> Result<(), D::Error> {
+    let name = match field {
+        Field::Year => "year",
+        Field::Month => "month",
+        Field::Day => "day",
+        Field::Hour => "hour",
+        Field::Minute => "minute",
+    };
+    display.clear()?;
+    display.print(&format!("Set {}:", name))
+}
+
+/// Print the date and time currently selected in the clock setting screen.
+pub fn update_clock_setting<D: CharDisplay>(display: &mut D, date_time: &DateTime) -> Result<(), D::Error> {
+    display.set_cursor(1, 0)?;
+    display.print(&format_date_time(date_time))
+}
+
+pub fn show_alarm_clock_setting<D: CharDisplay>(display: &mut D, field: Field) -> Result<(), D::Error> {
+    display.clear()?;
+    display.print(if field == Field::Hour { "Alarm hour:" } else { "Alarm minute:" })
+}
+
+/// Print the time of day currently selected in the alarm clock setting screen.
+pub fn update_alarm_clock_setting<D: CharDisplay>(display: &mut D, time: &DateTime) -> Result<(), D::Error> {
+    display.set_cursor(1, 5)?;
+    display.print(&format!("{:02}:{:02}", time.hour, time.minute))
+}
+
+pub fn show_alarm_clock<D: CharDisplay>(display: &mut D, time: &DateTime) -> Result<(), D::Error> {
+    display.clear()?;
+    display.print(&format!("Alarm at {:02}:{:02}", time.hour, time.minute))
+}
+
+/// Print the time left until the alarm clock rings.
+pub fn update_alarm_clock<D: CharDisplay>(display: &mut D, seconds_left: u32) -> Result<(), D::Error> {
+    display.set_cursor(1, 0)?;
+    display.print(&format!(
+        "{: >2}:{:02}:{:02} left",
+        seconds_left / 3600,
+        seconds_left / 60 % 60,
+        seconds_left % 60
+    ))
+}
+
+/// Setting of the minutes of the timer with the given label.
+pub fn show_setting<D: CharDisplay>(display: &mut D, label: &str) -> Result<(), D::Error> {
+    display.clear()?;
+    display.print(&format!("Set {}:", label))?;
+    display.set_cursor(1, 9)?;
+    display.print("min")
+}
+
+/// Print the minutes currently selected in the setting screen.
+pub fn update_setting<D: CharDisplay>(display: &mut D, minutes: u32) -> Result<(), D::Error> {
+    display.set_cursor(1, 5)?;
+    display.print(&format!(
+        "{: >3}", // right-aligned with 3 digits (including sign)
+        minutes
+    ))
+}
+
+/// Timer on screen, with the number of timers active (running or paused) at the top-right.
+pub fn show_count<D: CharDisplay>(display: &mut D, label: &str, active: usize) -> Result<(), D::Error> {
+    let indicator = format!("{} on", active);
+    let (_, columns) = display.dimensions();
+    display.clear()?;
+    display.print(label)?;
+    display.set_cursor(0, columns - indicator.len() as u8)?;
+    display.print(&indicator)
+}
+
+/// Print the time left to a timer, or whether it is not running.
+pub fn update_timer<D: CharDisplay>(display: &mut D, timer: &Timer, seconds_left: u32) -> Result<(), D::Error> {
+    display.set_cursor(1, 0)?;
+    match timer.status {
+        Status::Running { .. } => display.print(&format!("{} left", format_duration(seconds_left))),
+        Status::Paused { .. } => display.print(&format!("{} paused", format_duration(seconds_left))),
+        Status::Idle | Status::Expired => display.print("Click to set"),
+    }
+}
+
+/// Timer whose minutes are being adjusted while it runs, with a hint at the top-right.
+pub fn show_adjusting<D: CharDisplay>(display: &mut D, label: &str, seconds_left: u32) -> Result<(), D::Error> {
+    const HINT: &str = "+/-";
+    let (_, columns) = display.dimensions();
+    display.clear()?;
+    display.print(label)?;
+    display.set_cursor(0, columns - HINT.len() as u8)?;
+    display.print(HINT)?;
+    display.set_cursor(1, 0)?;
+    display.print(&format!("{} left", format_duration(seconds_left)))
+}
+
+/// Countdown paused, with the time left shown or not (to make it blink).
+pub fn show_paused<D: CharDisplay>(display: &mut D, seconds_left: u32, shown: bool) -> Result<(), D::Error> {
+    const MESSAGE: &str = "PAUSED";
+    let (_, columns) = display.dimensions();
+    display.clear()?;
+    display.set_cursor(0, (columns - MESSAGE.len() as u8) / 2)?;
+    display.print(MESSAGE)?;
+    if shown {
+        display.set_cursor(1, 0)?;
+        display.print(&format!("{} left", format_duration(seconds_left)))?;
+    }
+    Ok(())
+}
+
+/// `mm:ss`, or `h:mm:ss` from an hour up.
+fn format_duration(seconds: u32) -> alloc::string::String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Character animation, at the bottom-right of the screen.
+pub fn update_animation<D: CharDisplay>(display: &mut D, standing: bool) -> Result<(), D::Error> {
+    let (_, columns) = display.dimensions();
+    display.set_cursor(1, columns - 1)?;
+    if standing {
+        display.write_custom_char(MAN_STANDING)
+    } else {
+        display.write_custom_char(MAN_DANCING)
+    }
+}
+
+/// Alarm ringing, below which the label of the timer that fired, if not the alarm clock.
+pub fn show_alarm<D: CharDisplay>(display: &mut D, label: Option<&str>) -> Result<(), D::Error> {
+    const MESSAGE: &str = "TIME IS UP!!";
+    let (_, columns) = display.dimensions();
+    display.clear()?;
+    display.set_cursor(0, (columns - MESSAGE.len() as u8) / 2)?;
+    display.print(MESSAGE)?;
+    if let Some(label) = label {
+        display.set_cursor(1, (columns - label.len() as u8) / 2)?;
+        display.print(label)?;
+    }
+    Ok(())
+}
+
+pub fn show_pomodoro_setting<D: CharDisplay>(display: &mut D, field: pomodoro::Field) -> Result<(), D::Error> {
+    display.clear()?;
+    display.print(match field {
+        pomodoro::Field::Work => "Pomodoro work:",
+        pomodoro::Field::ShortBreak => "Short break:",
+        pomodoro::Field::LongBreak => "Long break:",
+        pomodoro::Field::Sessions => "Sessions/cycle:",
+        pomodoro::Field::ClickToStart => "Click to start:",
+    })
+}
+
+/// Print the value of the Pomodoro setting being edited.
+pub fn update_pomodoro_setting<D: CharDisplay>(
+    display: &mut D,
+    settings: &pomodoro::Settings,
+    field: pomodoro::Field,
+) -> Result<(), D::Error> {
+    display.set_cursor(1, 5)?;
+    match field {
+        pomodoro::Field::Work => display.print(&format!("{: >3} min", settings.work)),
+        pomodoro::Field::ShortBreak => display.print(&format!("{: >3} min", settings.short_break)),
+        pomodoro::Field::LongBreak => display.print(&format!("{: >3} min", settings.long_break)),
+        pomodoro::Field::Sessions => display.print(&format!("{: >3}", settings.sessions)),
+        pomodoro::Field::ClickToStart => display.print(if settings.click_to_start { "yes" } else { " no" }),
+    }
+}
+
+fn phase_name(phase: Phase) -> &'static str {
+    match phase {
+        Phase::Work => "Work",
+        Phase::ShortBreak => "Break",
+        Phase::LongBreak => "Long break",
+    }
+}
+
+/// Phase of the Pomodoro, with the work session of the cycle at the top-right.
+pub fn show_pomodoro<D: CharDisplay>(display: &mut D, session: &Session, sessions: u32) -> Result<(), D::Error> {
+    let counter = format!("{}/{}", session.number, sessions);
+    let (_, columns) = display.dimensions();
+    display.clear()?;
+    display.print(phase_name(session.phase))?;
+    display.set_cursor(0, columns - counter.len() as u8)?;
+    display.print(&counter)
+}
+
+/// Alert at the start of a phase of the Pomodoro.
+pub fn show_pomodoro_alert<D: CharDisplay>(display: &mut D, session: &Session, sessions: u32) -> Result<(), D::Error> {
+    let message = match session.phase {
+        Phase::Work => "Back to work!",
+        Phase::ShortBreak => "Take a break!",
+        Phase::LongBreak => "Long break!",
+    };
+    let counter = format!("Session {}/{}", session.number, sessions);
+    let (_, columns) = display.dimensions();
+    display.clear()?;
+    display.set_cursor(0, (columns - message.len() as u8) / 2)?;
+    display.print(message)?;
+    display.set_cursor(1, (columns - counter.len() as u8) / 2)?;
+    display.print(&counter)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::String;
+
+    use super::*;
+    use crate::input::UiEvent;
+    use crate::millis::Instant;
+    use crate::timesaver::{Event, Now};
+
+    fn rows(buffer: &TextBuffer<2, 16>) -> [String; 2] {
+        let row = |row| buffer.row(row).iter().map(|&code| code as char).collect();
+        [row(0), row(1)]
+    }
+
+    fn rendered(timesaver: &TimeSaver, wall_clock: Option<DateTime>) -> [String; 2] {
+        let mut buffer = TextBuffer::new();
+        render(&mut buffer, timesaver, wall_clock).unwrap();
+        rows(&buffer)
+    }
+
+    #[test]
+    fn screens_follow_the_state() {
+        let now = Now { instant: Instant::ZERO, wall_clock: Some(DateTime::new(2024, 5, 17, 12, 34, 56)) };
+        let mut timesaver = TimeSaver::new();
+        timesaver.start(now);
+        assert_eq!(rendered(&timesaver, now.wall_clock), ["Save your time \u{3}", "2024-05-17 12:34"]);
+        assert_eq!(rendered(&timesaver, None)[1], "Clock not set   ");
+
+        timesaver.handle(Event::Ui(UiEvent::Select, now.instant), now);
+        assert_eq!(rendered(&timesaver, now.wall_clock), ["Set Timer 1:    ", "      20 min    "]);
+
+        timesaver.handle(Event::Ui(UiEvent::Select, now.instant), now);
+        assert_eq!(rendered(&timesaver, now.wall_clock), ["Timer 1     1 on", "20:00 left     \u{1}"]);
+
+        timesaver.handle(Event::Ui(UiEvent::HeldUp(1), now.instant), now);
+        assert_eq!(rendered(&timesaver, now.wall_clock), ["Timer 1      +/-", "21:00 left      "]);
+    }
+
+    #[test]
+    fn copy_only_writes_the_rows_that_changed() {
+        let mut display = TextBuffer::<2, 16>::new();
+        let mut copied = TextBuffer::<2, 16>::new();
+        let mut screen = TextBuffer::<2, 16>::new();
+        screen.print("Hello").unwrap();
+        screen.set_backlight(false).unwrap();
+        // Left alone, as the second row is blank on both screens
+        display.set_cursor(1, 0).unwrap();
+        display.print("untouched").unwrap();
+
+        copy(&mut display, &screen, &mut copied).unwrap();
+        assert_eq!(rows(&display), ["Hello           ", "untouched       "]);
+        assert!(!display.backlight());
+        assert_eq!(rows(&copied), rows(&screen));
+    }
+}